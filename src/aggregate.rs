@@ -0,0 +1,339 @@
+//! Aggregation of session durations over the report range
+
+use crate::clip::{local_date, next_midnight};
+use crate::{
+    Clock, DateRange, MetadataValue, ReportError, Session, Tag, TagHierarchy, TagNode,
+    TimewarriorData,
+};
+use chrono::{DateTime, Datelike, Duration, IsoWeek, NaiveDate, TimeZone, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+
+impl TimewarriorData {
+    /// Total duration of all sessions within the report range
+    ///
+    /// Sessions are clipped to `temp.report.start` and `temp.report.end`, sessions which have
+    /// not ended yet are treated as running until the current time of the clock.
+    pub fn total_duration(&self, clock: &dyn Clock) -> Result<Duration, ReportError> {
+        Ok(self
+            .clipped_spans(clock.now())?
+            .iter()
+            .fold(Duration::zero(), |total, (_, span)| total + span.duration()))
+    }
+
+    /// Duration of all sessions within the report range per tag
+    ///
+    /// A session with several tags counts towards each of them, but only once towards a tag it
+    /// lists twice. Sessions without tags are not included.
+    pub fn duration_by_tag(
+        &self,
+        clock: &dyn Clock,
+    ) -> Result<BTreeMap<String, Duration>, ReportError> {
+        let mut durations = BTreeMap::new();
+        for (session, span) in self.clipped_spans(clock.now())? {
+            let tags = session
+                .tags
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect::<BTreeSet<String>>();
+            for tag in tags {
+                add(&mut durations, tag, span.duration());
+            }
+        }
+        Ok(durations)
+    }
+
+    /// Duration of all sessions within the report range per combination of tags
+    ///
+    /// The tags of each combination are sorted, sessions without tags are collected under an
+    /// empty combination.
+    pub fn duration_by_tag_combination(
+        &self,
+        clock: &dyn Clock,
+    ) -> Result<BTreeMap<Vec<String>, Duration>, ReportError> {
+        let mut durations = BTreeMap::new();
+        for (session, span) in self.clipped_spans(clock.now())? {
+            let mut tags = session
+                .tags
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect::<Vec<String>>();
+            tags.sort();
+            tags.dedup();
+            add(&mut durations, tags, span.duration());
+        }
+        Ok(durations)
+    }
+
+    /// Duration of all sessions within the report range rolled up along the tag hierarchy
+    ///
+    /// Each tag counts towards itself and every level above it, so `acme` contains the time of
+    /// `acme.web` and `acme.web.login`. A session counts only once towards each level, even if
+    /// several of its tags lie below it. The returned trees are sorted by tag.
+    pub fn duration_by_tag_tree(
+        &self,
+        clock: &dyn Clock,
+        hierarchy: &TagHierarchy,
+    ) -> Result<Vec<TagNode>, ReportError> {
+        let mut durations = BTreeMap::new();
+        for (session, span) in self.clipped_spans(clock.now())? {
+            let levels = session
+                .tags
+                .iter()
+                .flat_map(|tag| tag.path(hierarchy))
+                .collect::<BTreeSet<Tag>>();
+            for level in levels {
+                add(&mut durations, level, span.duration());
+            }
+        }
+        let durations = durations.into_iter().collect::<Vec<(Tag, Duration)>>();
+        Ok(TagNode::build(&durations, None, hierarchy))
+    }
+
+    /// Duration of all sessions within the report range per value of a metadata key
+    ///
+    /// Sessions without the key are not included. The metadata has to be parsed from the
+    /// annotations first, see [`TimewarriorData::with_metadata`].
+    pub fn duration_by_metadata(
+        &self,
+        key: &str,
+        clock: &dyn Clock,
+    ) -> Result<BTreeMap<MetadataValue, Duration>, ReportError> {
+        let mut durations = BTreeMap::new();
+        for (session, span) in self.clipped_spans(clock.now())? {
+            if let Some(value) = session.metadata.get(key) {
+                add(&mut durations, value.clone(), span.duration());
+            }
+        }
+        Ok(durations)
+    }
+
+    /// Duration of all sessions within the report range per day
+    ///
+    /// Days are taken in the given timezone, sessions spanning midnight there are split, so
+    /// each part counts towards its own day.
+    pub fn duration_by_day<Tz: TimeZone>(
+        &self,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<BTreeMap<NaiveDate, Duration>, ReportError> {
+        let mut durations = BTreeMap::new();
+        for span in self.daily_spans(clock.now(), timezone)? {
+            add(
+                &mut durations,
+                local_date(span.start, timezone),
+                span.duration(),
+            );
+        }
+        Ok(durations)
+    }
+
+    /// Duration of all sessions within the report range per ISO week
+    ///
+    /// Weeks are taken in the given timezone, sessions spanning midnight there are split, so
+    /// each part counts towards its own week.
+    pub fn duration_by_week<Tz: TimeZone>(
+        &self,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<BTreeMap<IsoWeek, Duration>, ReportError> {
+        let mut durations = BTreeMap::new();
+        for span in self.daily_spans(clock.now(), timezone)? {
+            add(
+                &mut durations,
+                local_date(span.start, timezone).iso_week(),
+                span.duration(),
+            );
+        }
+        Ok(durations)
+    }
+
+    /// The clipped spans of all sessions, split at midnight in the timezone
+    fn daily_spans<Tz: TimeZone>(
+        &self,
+        now: DateTime<Utc>,
+        timezone: &Tz,
+    ) -> Result<Vec<DateRange>, ReportError> {
+        let mut spans = Vec::new();
+        for (_, span) in self.clipped_spans(now)? {
+            let mut start = span.start;
+            while start < span.end {
+                let end = next_midnight(start, timezone).min(span.end);
+                spans.push(DateRange::new(start, end));
+                start = end;
+            }
+        }
+        Ok(spans)
+    }
+
+    /// Start and end of all sessions clipped to the report range, leaving out empty ones
+    fn clipped_spans(&self, now: DateTime<Utc>) -> Result<Vec<(&Session, DateRange)>, ReportError> {
+        let report_start = self.config.report_start()?;
+        let report_end = self.config.report_end()?;
+        Ok(self
+            .sessions
+            .iter()
+            .filter_map(|session| {
+                let mut start = session.start;
+                let mut end = session.end.unwrap_or(now);
+                if let Some(report_start) = report_start {
+                    start = start.max(report_start);
+                }
+                if let Some(report_end) = report_end {
+                    end = end.min(report_end);
+                }
+                if end > start {
+                    Some((session, DateRange::new(start, end)))
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+pub(crate) fn add<K: Ord>(durations: &mut BTreeMap<K, Duration>, key: K, duration: Duration) {
+    let total = durations.entry(key).or_insert_with(Duration::zero);
+    *total = *total + duration;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FixedClock, ReportTimeZone};
+    use chrono::FixedOffset;
+
+    fn clock() -> FixedClock {
+        FixedClock(Utc.ymd(2021, 7, 12).and_hms(12, 0, 0))
+    }
+
+    fn report() -> TimewarriorData {
+        TimewarriorData::from_string(
+            "temp.report.start: 20210705T000000Z\n\
+             temp.report.end: 20210712T000000Z\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210704T230000Z\",\"end\":\"20210705T010000Z\",\"tags\":[\"a\"]},\n\
+             {\"id\":2,\"start\":\"20210707T100000Z\",\"end\":\"20210707T113000Z\",\"tags\":[\"a\",\"b\"]},\n\
+             {\"id\":1,\"start\":\"20210711T230000Z\",\"end\":\"20210712T020000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sum_clipped_durations() {
+        assert_eq!(
+            report().total_duration(&clock()).unwrap(),
+            Duration::minutes(210)
+        );
+    }
+
+    #[test]
+    fn evaluate_open_sessions_with_clock() {
+        let mut data = report();
+        data.sessions[2].end = None;
+        let now = Utc.ymd(2021, 7, 12).and_hms(0, 30, 0);
+        assert_eq!(
+            data.total_duration(&FixedClock(now)).unwrap(),
+            Duration::minutes(210)
+        );
+        let now = Utc.ymd(2021, 7, 11).and_hms(23, 30, 0);
+        assert_eq!(
+            data.total_duration(&FixedClock(now)).unwrap(),
+            Duration::minutes(180)
+        );
+    }
+
+    #[test]
+    fn sum_durations_by_tag() {
+        let durations = report().duration_by_tag(&clock()).unwrap();
+        assert_eq!(durations.len(), 2);
+        assert_eq!(durations["a"], Duration::minutes(150));
+        assert_eq!(durations["b"], Duration::minutes(90));
+
+        let mut data = report();
+        data.sessions[1].tags = vec!["a".into(), "b".into(), "a".into()];
+        let durations = data.duration_by_tag(&clock()).unwrap();
+        assert_eq!(durations["a"], Duration::minutes(150));
+    }
+
+    #[test]
+    fn sum_durations_by_tag_combination() {
+        let durations = report().duration_by_tag_combination(&clock()).unwrap();
+        assert_eq!(durations.len(), 3);
+        assert_eq!(durations[&vec!["a".to_string()]], Duration::minutes(60));
+        assert_eq!(
+            durations[&vec!["a".to_string(), "b".to_string()]],
+            Duration::minutes(90)
+        );
+        assert_eq!(durations[&Vec::<String>::new()], Duration::minutes(60));
+    }
+
+    #[test]
+    fn roll_up_durations_along_tag_hierarchy() {
+        let mut data = report();
+        data.sessions[0].tags = vec!["acme.web".into(), "acme.api".into()];
+        data.sessions[1].tags = vec!["acme.web.login".into(), "internal".into()];
+        let trees = data
+            .duration_by_tag_tree(&clock(), &TagHierarchy::default())
+            .unwrap();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[0].tag, "acme");
+        assert_eq!(trees[0].duration, Duration::minutes(150));
+        let children = &trees[0].children;
+        assert_eq!(children.len(), 2);
+        assert_eq!(
+            (children[0].tag.as_str(), children[0].duration),
+            ("acme.api", Duration::minutes(60))
+        );
+        assert_eq!(
+            (children[1].tag.as_str(), children[1].duration),
+            ("acme.web", Duration::minutes(150))
+        );
+        assert_eq!(children[1].children[0].duration, Duration::minutes(90));
+        assert_eq!(trees[1].tag, "internal");
+        assert!(trees[1].children.is_empty());
+    }
+
+    #[test]
+    fn sum_durations_by_day_in_timezone() {
+        let durations = report()
+            .duration_by_day(&clock(), &ReportTimeZone::Utc)
+            .unwrap();
+        assert_eq!(durations.len(), 3);
+        assert_eq!(
+            durations[&NaiveDate::from_ymd(2021, 7, 5)],
+            Duration::minutes(60)
+        );
+        assert_eq!(
+            durations[&NaiveDate::from_ymd(2021, 7, 11)],
+            Duration::minutes(60)
+        );
+
+        let durations = report()
+            .duration_by_day(&clock(), &FixedOffset::west(5 * 3600))
+            .unwrap();
+        assert_eq!(durations.len(), 3);
+        assert_eq!(
+            durations[&NaiveDate::from_ymd(2021, 7, 4)],
+            Duration::minutes(60)
+        );
+        assert_eq!(
+            durations[&NaiveDate::from_ymd(2021, 7, 11)],
+            Duration::minutes(60)
+        );
+    }
+
+    #[test]
+    fn sum_durations_by_week() {
+        let durations = report()
+            .duration_by_week(&clock(), &ReportTimeZone::Utc)
+            .unwrap();
+        assert_eq!(durations.len(), 1);
+        assert_eq!(
+            durations[&NaiveDate::from_ymd(2021, 7, 7).iso_week()],
+            Duration::minutes(210)
+        );
+    }
+}
@@ -0,0 +1,564 @@
+//! Hourly rates per tag and invoices computed from the tracked time
+//!
+//! All amounts are kept as integers in the minor unit of their currency, like cents, so no
+//! precision is lost to floating point arithmetic. Amounts are rounded half away from zero to
+//! the minor unit whenever a rate or a discount is applied, so negative amounts are rounded
+//! like positive ones.
+
+use crate::{
+    Clock, ReportConfig, ReportError, ReportTimeZone, Rounding, Session, Tag, TagHierarchy,
+    TimewarriorData,
+};
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of decimal places of the minor unit of all currencies
+const MINOR_DIGITS: u32 = 2;
+
+/// Parse a decimal number like `120.5` into an integer scaled by `10^digits`
+///
+/// More decimal places than `digits` are rejected rather than rounded.
+fn parse_decimal(value: &str, digits: u32) -> Option<i64> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > digits as usize
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let whole: i64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let fraction: i64 = format!("{:0<width$}", fraction, width = digits as usize)
+        .parse()
+        .unwrap_or(0);
+    let scaled = whole
+        .checked_mul(10_i64.pow(digits))?
+        .checked_add(fraction)?;
+    Some(if negative { -scaled } else { scaled })
+}
+
+/// Write an integer scaled by `10^digits` as a decimal number
+fn format_decimal(f: &mut fmt::Formatter, value: i128, digits: u32) -> fmt::Result {
+    let scale = 10_i128.pow(digits);
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.abs();
+    write!(f, "{}{}", sign, value / scale)?;
+    if digits > 0 {
+        write!(f, ".{:0width$}", value % scale, width = digits as usize)?;
+    }
+    Ok(())
+}
+
+/// Divide, rounding half away from zero
+fn divide_rounded(dividend: i128, divisor: i128) -> i128 {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder.abs() * 2 >= divisor.abs() {
+        quotient + dividend.signum() * divisor.signum()
+    } else {
+        quotient
+    }
+}
+
+/// An amount of money in the minor unit of a currency
+///
+/// # Example
+///
+/// ```rust
+/// use timewarrior_report::Money;
+///
+/// let rate: Money = "120.5 EUR".parse().unwrap();
+/// assert_eq!(rate.minor, 12050);
+/// assert_eq!(rate.to_string(), "120.50 EUR");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money {
+    /// The amount in the minor unit, e.g. in cents
+    pub minor: i64,
+    /// The currency code, like `EUR`
+    pub currency: String,
+}
+
+impl Money {
+    /// An amount given in the minor unit of the currency
+    pub fn new(minor: i64, currency: &str) -> Self {
+        Money {
+            minor,
+            currency: currency.into(),
+        }
+    }
+
+    /// Parse an amount like `120.50 EUR`, using the currency given if the value has none
+    pub fn parse_with_currency(value: &str, currency: &str) -> Option<Self> {
+        let mut words = value.split_whitespace();
+        let amount = parse_decimal(words.next()?, MINOR_DIGITS)?;
+        let currency = match (words.next(), words.next()) {
+            (Some(currency), None) => currency,
+            (None, None) if !currency.is_empty() => currency,
+            _ => return None,
+        };
+        Some(Money::new(amount, currency))
+    }
+
+    /// The price of the duration at this hourly rate
+    pub fn for_duration(&self, duration: Duration) -> Money {
+        let minor = divide_rounded(
+            i128::from(self.minor) * i128::from(duration.num_seconds()),
+            3600,
+        );
+        Money::new(minor as i64, &self.currency)
+    }
+
+    /// The share of the amount given by the percentage
+    pub fn percentage(&self, percentage: Percentage) -> Money {
+        let minor = divide_rounded(
+            i128::from(self.minor) * i128::from(percentage.hundredths),
+            10_000,
+        );
+        Money::new(minor as i64, &self.currency)
+    }
+}
+
+impl FromStr for Money {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Money::parse_with_currency(value, "").ok_or_else(|| {
+            ReportError::Other(format!(
+                "invalid amount \"{}\", expected e.g. \"120.50 EUR\"",
+                value
+            ))
+        })
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        format_decimal(f, i128::from(self.minor), MINOR_DIGITS)?;
+        write!(f, " {}", self.currency)
+    }
+}
+
+/// A percentage with up to two decimal places, like a discount of `12.5 %`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Percentage {
+    /// The percentage in hundredths of a percent, 1250 for `12.5 %`
+    pub hundredths: i64,
+}
+
+impl Percentage {
+    /// Whether the percentage is zero
+    pub fn is_zero(&self) -> bool {
+        self.hundredths == 0
+    }
+}
+
+impl FromStr for Percentage {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let number = value.trim().trim_end_matches('%');
+        parse_decimal(number, 2)
+            .filter(|hundredths| (0..=10_000).contains(hundredths))
+            .map(|hundredths| Percentage { hundredths })
+            .ok_or_else(|| {
+                ReportError::Other(format!(
+                    "invalid percentage \"{}\", expected e.g. \"12.5%\"",
+                    value
+                ))
+            })
+    }
+}
+
+impl fmt::Display for Percentage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.hundredths % 100 == 0 {
+            write!(f, "{}", self.hundredths / 100)?;
+        } else if self.hundredths % 10 == 0 {
+            format_decimal(f, i128::from(self.hundredths / 10), 1)?;
+        } else {
+            format_decimal(f, i128::from(self.hundredths), 2)?;
+        }
+        write!(f, " %")
+    }
+}
+
+/// The rules for billing tracked time, read from the `reports.invoice.*` keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BillingRules {
+    /// Hourly rates per tag, each applying to the tag and all tags below it
+    pub rates: BTreeMap<String, Money>,
+    /// Hourly rate for sessions without a tag with a rate, not billed if `None`
+    pub default_rate: Option<Money>,
+    /// Discounts per tag with a rate
+    pub discounts: BTreeMap<String, Percentage>,
+    /// Discount on the total of each currency
+    pub discount: Percentage,
+    /// Rounding of the billed time, per session, day or line item depending on its scope
+    pub rounding: Rounding,
+    /// The hierarchy of tags, deciding which tags a rate applies to
+    pub hierarchy: TagHierarchy,
+}
+
+impl BillingRules {
+    /// Read the billing rules from the configuration
+    ///
+    /// `reports.invoice.rate.<tag>` sets the hourly rate for a tag and all tags below it, like
+    /// `120` or `120.50 USD`, the most specific rate of a session is used. Amounts without a
+    /// currency are in `reports.invoice.currency`. `reports.invoice.rate` bills sessions without
+    /// a matching tag, which are left out otherwise. `reports.invoice.discount.<tag>` sets a
+    /// discount on the line item of a tag and `reports.invoice.discount` on the total, like
+    /// `10%`. The billed time is rounded as described in [`Rounding::from_config`], per
+    /// session, per day or per line item depending on `reports.rounding.scope`, which defaults
+    /// to per session.
+    pub fn from_config(config: &ReportConfig) -> Result<Self, ReportError> {
+        let currency = config.get("reports.invoice.currency").unwrap_or("").trim();
+        let money = |key: &str, value: &str| {
+            Money::parse_with_currency(value, currency).ok_or_else(|| ReportError::InvalidConfig {
+                key: key.into(),
+                value: value.into(),
+                expected: if currency.is_empty() {
+                    "an amount with a currency like \"120.50 EUR\", or reports.invoice.currency"
+                        .into()
+                } else {
+                    "an amount like \"120.50\"".into()
+                },
+            })
+        };
+        let percentage = |key: &str, value: &str| {
+            value
+                .parse::<Percentage>()
+                .map_err(|_| ReportError::InvalidConfig {
+                    key: key.into(),
+                    value: value.into(),
+                    expected: "a percentage like \"10%\"".into(),
+                })
+        };
+        let mut rules = BillingRules {
+            rates: BTreeMap::new(),
+            default_rate: None,
+            discounts: BTreeMap::new(),
+            discount: Percentage::default(),
+            rounding: Rounding::from_config(config)?,
+            hierarchy: config.tag_hierarchy(),
+        };
+        for (key, value) in config.raw() {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            if let Some(tag) = key.strip_prefix("reports.invoice.rate.") {
+                rules.rates.insert(tag.into(), money(key, value)?);
+            } else if let Some(tag) = key.strip_prefix("reports.invoice.discount.") {
+                rules.discounts.insert(tag.into(), percentage(key, value)?);
+            } else if key == "reports.invoice.rate" {
+                rules.default_rate = Some(money(key, value)?);
+            } else if key == "reports.invoice.discount" {
+                rules.discount = percentage(key, value)?;
+            }
+        }
+        Ok(rules)
+    }
+
+    /// The tag with a rate the session is billed under, `None` if no rate applies
+    ///
+    /// Of all tags with a rate that one of the tags of the session lies under, the most
+    /// specific one is used. If several of them are equally specific, like two clients tagged
+    /// on the same session, the session cannot be billed and an error is returned.
+    pub fn billed_tag(&self, session: &Session) -> Result<Option<&str>, ReportError> {
+        let depth = |rated: &str| Tag::from(rated).depth(&self.hierarchy);
+        let matching = self
+            .rates
+            .keys()
+            .map(String::as_str)
+            .filter(|rated| {
+                session
+                    .tags
+                    .iter()
+                    .any(|tag| tag.is_under(rated, &self.hierarchy))
+            })
+            .collect::<Vec<&str>>();
+        let deepest = match matching.iter().map(|rated| depth(rated)).max() {
+            Some(deepest) => deepest,
+            None => return Ok(None),
+        };
+        let mut most_specific = matching.into_iter().filter(|rated| depth(rated) == deepest);
+        let billed = most_specific.next();
+        let others = most_specific.collect::<Vec<&str>>();
+        if !others.is_empty() {
+            return Err(ReportError::Other(format!(
+                "session @{} matches the rates of \"{}\" and \"{}\" equally",
+                session.id,
+                billed.unwrap_or_default(),
+                others.join("\", \"")
+            )));
+        }
+        Ok(billed)
+    }
+}
+
+/// A line of an invoice, billing the time of one tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineItem {
+    /// The tag with a rate, `None` for sessions billed at the default rate
+    pub tag: Option<String>,
+    /// The billed time, after rounding
+    pub duration: Duration,
+    /// The hourly rate
+    pub rate: Money,
+    /// The discount on the line item
+    pub discount: Percentage,
+    /// The price of the time, after the discount
+    pub amount: Money,
+}
+
+/// The sum of all line items in one currency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceTotal {
+    /// The sum of the amounts of the line items
+    pub subtotal: Money,
+    /// The discount on the subtotal
+    pub discount: Money,
+    /// The amount to be paid
+    pub total: Money,
+}
+
+/// An invoice over the tracked time within the report range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invoice {
+    /// The line items, sorted by tag with the default rate last
+    pub items: Vec<LineItem>,
+    /// The discount on the totals
+    pub discount: Percentage,
+    /// The totals per currency, sorted by currency
+    pub totals: Vec<InvoiceTotal>,
+}
+
+impl TimewarriorData {
+    /// Compute an invoice over the sessions within the report range
+    ///
+    /// Each session is billed at the rate of its most specific tag with a rate, see
+    /// [`BillingRules::billed_tag`]. The timezone is used to round the time per day.
+    pub fn invoice(
+        &self,
+        rules: &BillingRules,
+        clock: &dyn Clock,
+        timezone: &ReportTimeZone,
+    ) -> Result<Invoice, ReportError> {
+        let durations = self.rounded_durations(&rules.rounding, clock, timezone, |session| {
+            Ok(match rules.billed_tag(session)? {
+                Some(tag) => vec![Some(tag.to_string())],
+                None if rules.default_rate.is_some() => vec![None],
+                None => vec![],
+            })
+        })?;
+        let mut items = Vec::new();
+        for (tag, duration) in durations {
+            let rate = match &tag {
+                Some(tag) => rules.rates[tag].clone(),
+                None => rules
+                    .default_rate
+                    .clone()
+                    .expect("only billed with a default rate"),
+            };
+            let discount = tag
+                .as_ref()
+                .and_then(|tag| rules.discounts.get(tag))
+                .copied()
+                .unwrap_or_default();
+            let price = rate.for_duration(duration);
+            let amount = Money::new(
+                price.minor - price.percentage(discount).minor,
+                &rate.currency,
+            );
+            items.push(LineItem {
+                tag,
+                duration,
+                rate,
+                discount,
+                amount,
+            });
+        }
+        items.sort_by(|a, b| (a.tag.is_none(), &a.tag).cmp(&(b.tag.is_none(), &b.tag)));
+
+        let mut subtotals = BTreeMap::new();
+        for item in &items {
+            *subtotals.entry(item.amount.currency.clone()).or_insert(0) += item.amount.minor;
+        }
+        let totals = subtotals
+            .into_iter()
+            .map(|(currency, minor)| {
+                let subtotal = Money::new(minor, &currency);
+                let discount = subtotal.percentage(rules.discount);
+                InvoiceTotal {
+                    total: Money::new(subtotal.minor - discount.minor, &currency),
+                    subtotal,
+                    discount,
+                }
+            })
+            .collect();
+        Ok(Invoice {
+            items,
+            discount: rules.discount,
+            totals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SystemClock;
+
+    fn report(config: &[(&str, &str)]) -> TimewarriorData {
+        let mut data = TimewarriorData::from_string(
+            "[\n\
+             {\"id\":4,\"start\":\"20210711T080000Z\",\"end\":\"20210711T091000Z\",\"tags\":[\"acme.web\"]},\n\
+             {\"id\":3,\"start\":\"20210711T100000Z\",\"end\":\"20210711T102000Z\",\"tags\":[\"acme.web.login\",\"review\"]},\n\
+             {\"id\":2,\"start\":\"20210711T110000Z\",\"end\":\"20210711T113000Z\",\"tags\":[\"globex\"]},\n\
+             {\"id\":1,\"start\":\"20210711T120000Z\",\"end\":\"20210711T124000Z\",\"tags\":[\"internal\"]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        for (key, value) in config {
+            data.config.insert(key.to_string(), value.to_string());
+        }
+        data
+    }
+
+    #[test]
+    fn parse_amounts_and_percentages() {
+        assert_eq!(parse_decimal("120", 2), Some(12000));
+        assert_eq!(parse_decimal("0.5", 2), Some(50));
+        assert_eq!(parse_decimal("-.05", 2), Some(-5));
+        assert_eq!(parse_decimal("1.005", 2), None);
+        assert_eq!(parse_decimal("1e3", 2), None);
+        assert_eq!(
+            Money::parse_with_currency("99.9", "EUR"),
+            Some(Money::new(9990, "EUR"))
+        );
+        assert_eq!(Money::parse_with_currency("99.9", ""), None);
+        let percentage = "12.5%".parse::<Percentage>().unwrap();
+        assert_eq!(percentage.hundredths, 1250);
+        assert_eq!(percentage.to_string(), "12.5 %");
+        assert!("120%".parse::<Percentage>().is_err());
+        assert_eq!(Money::new(-5, "EUR").to_string(), "-0.05 EUR");
+    }
+
+    #[test]
+    fn compute_exact_amounts() {
+        let rate = Money::new(10_000, "EUR");
+        assert_eq!(rate.for_duration(Duration::seconds(1)).minor, 3);
+        assert_eq!(rate.for_duration(Duration::seconds(18)).minor, 50);
+        assert_eq!(rate.for_duration(Duration::minutes(7)).minor, 1167);
+        assert_eq!(
+            Money::new(1005, "EUR")
+                .percentage("50%".parse().unwrap())
+                .minor,
+            503
+        );
+    }
+
+    #[test]
+    fn bill_sessions_per_tag() {
+        let data = report(&[
+            ("reports.invoice.currency", "EUR"),
+            ("reports.invoice.rate.acme", "100"),
+            ("reports.invoice.rate.acme.web.login", "120"),
+            ("reports.invoice.rate.globex", "90.50 USD"),
+            ("reports.invoice.discount.acme", "10%"),
+            ("reports.invoice.discount", "5%"),
+            ("reports.rounding.increment", "15min"),
+        ]);
+        let rules = BillingRules::from_config(&data.config).unwrap();
+        let invoice = data
+            .invoice(&rules, &SystemClock, &ReportTimeZone::Utc)
+            .unwrap();
+        let items = invoice
+            .items
+            .iter()
+            .map(|item| {
+                (
+                    item.tag.clone().unwrap_or_default(),
+                    item.duration.num_minutes(),
+                    item.amount.to_string(),
+                )
+            })
+            .collect::<Vec<(String, i64, String)>>();
+        assert_eq!(
+            items,
+            vec![
+                ("acme".into(), 75, "112.50 EUR".into()),
+                ("acme.web.login".into(), 30, "60.00 EUR".into()),
+                ("globex".into(), 30, "45.25 USD".into()),
+            ]
+        );
+        assert_eq!(invoice.totals.len(), 2);
+        assert_eq!(invoice.totals[0].subtotal, Money::new(17250, "EUR"));
+        assert_eq!(invoice.totals[0].discount, Money::new(863, "EUR"));
+        assert_eq!(invoice.totals[0].total, Money::new(16387, "EUR"));
+        assert_eq!(invoice.totals[1].total, Money::new(4299, "USD"));
+
+        let data = report(&[("reports.invoice.rate", "80 EUR")]);
+        let rules = BillingRules::from_config(&data.config).unwrap();
+        let invoice = data
+            .invoice(&rules, &SystemClock, &ReportTimeZone::Utc)
+            .unwrap();
+        assert_eq!(invoice.items.len(), 1);
+        assert_eq!(invoice.items[0].tag, None);
+        assert_eq!(invoice.totals[0].total, Money::new(21333, "EUR"));
+    }
+
+    #[test]
+    fn reject_equally_specific_rates() {
+        let data = report(&[
+            ("reports.invoice.rate.acme", "100 EUR"),
+            ("reports.invoice.rate.review", "80 EUR"),
+        ]);
+        let rules = BillingRules::from_config(&data.config).unwrap();
+        assert_eq!(rules.billed_tag(&data.sessions[0]).unwrap(), Some("acme"));
+        assert_eq!(
+            rules.billed_tag(&data.sessions[1]).unwrap_err().to_string(),
+            "Other Error: session @3 matches the rates of \"acme\" and \"review\" equally"
+        );
+        assert!(data
+            .invoice(&rules, &SystemClock, &ReportTimeZone::Utc)
+            .is_err());
+
+        let data = report(&[
+            ("reports.invoice.rate.acme", "100 EUR"),
+            ("reports.invoice.rate.acme.web.login", "120 EUR"),
+            ("reports.invoice.rate.review", "80 EUR"),
+        ]);
+        let rules = BillingRules::from_config(&data.config).unwrap();
+        assert_eq!(
+            rules.billed_tag(&data.sessions[1]).unwrap(),
+            Some("acme.web.login")
+        );
+    }
+
+    #[test]
+    fn reject_invalid_rates() {
+        let data = report(&[("reports.invoice.rate.acme", "100")]);
+        assert!(BillingRules::from_config(&data.config).is_err());
+        let data = report(&[
+            ("reports.invoice.rate.acme", "100 EUR"),
+            ("reports.invoice.discount", "ten"),
+        ]);
+        assert!(BillingRules::from_config(&data.config).is_err());
+    }
+}
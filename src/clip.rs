@@ -0,0 +1,217 @@
+//! Clipping of sessions to the report range and to day boundaries
+
+use crate::{Clock, ReportError, Session, TimewarriorData};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+impl TimewarriorData {
+    /// A copy of the data with all sessions clipped to the report range
+    ///
+    /// Sessions starting before `temp.report.start` or ending after `temp.report.end` are
+    /// shortened to the range, sessions lying completely outside of it are left out. A session
+    /// which has not ended yet is ended at `temp.report.end` if that lies before the current
+    /// time of the clock.
+    pub fn clipped_to_range(&self, clock: &dyn Clock) -> Result<TimewarriorData, ReportError> {
+        let report_start = self.config.report_start()?;
+        let report_end = self.config.report_end()?;
+        let now = clock.now();
+        let sessions = self
+            .sessions
+            .iter()
+            .filter_map(|session| {
+                let mut session = session.clone();
+                if let Some(report_start) = report_start {
+                    session.start = session.start.max(report_start);
+                }
+                if let Some(report_end) = report_end {
+                    session.end = match session.end {
+                        Some(end) => Some(end.min(report_end)),
+                        None if report_end <= now => Some(report_end),
+                        None => None,
+                    };
+                }
+                match session.end {
+                    Some(end) if end <= session.start => None,
+                    _ => Some(session),
+                }
+            })
+            .collect();
+        Ok(TimewarriorData {
+            config: self.config.clone(),
+            sessions,
+        })
+    }
+}
+
+impl Session {
+    /// Split the session into parts which do not span midnight in the given timezone
+    ///
+    /// All parts keep the ID, tags and annotation of the session. If the session has not ended
+    /// yet, it is split up to the current time of the clock and the last part is left open.
+    pub fn split_at_midnight<'a, Tz: TimeZone>(
+        &'a self,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> SplitAtMidnight<'a, Tz> {
+        SplitAtMidnight {
+            session: self,
+            next_start: Some(self.start),
+            now: clock.now(),
+            timezone: timezone.clone(),
+        }
+    }
+}
+
+/// An iterator over the parts of a session split at midnight
+///
+/// Created by [`Session::split_at_midnight`].
+#[derive(Debug)]
+pub struct SplitAtMidnight<'a, Tz: TimeZone> {
+    session: &'a Session,
+    next_start: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    timezone: Tz,
+}
+
+impl<'a, Tz: TimeZone> Iterator for SplitAtMidnight<'a, Tz> {
+    type Item = Session;
+
+    fn next(&mut self) -> Option<Session> {
+        let start = self.next_start.take()?;
+        let midnight = next_midnight(start, &self.timezone);
+        let end = match self.session.end {
+            Some(end) if end <= midnight => Some(end),
+            Some(_) => {
+                self.next_start = Some(midnight);
+                Some(midnight)
+            }
+            None if self.now < midnight => None,
+            None => {
+                self.next_start = Some(midnight);
+                Some(midnight)
+            }
+        };
+        Some(Session {
+            start,
+            end,
+            ..self.session.clone()
+        })
+    }
+}
+
+/// The day the given instant falls on in the timezone
+pub(crate) fn local_date<Tz: TimeZone>(instant: DateTime<Utc>, timezone: &Tz) -> NaiveDate {
+    instant.with_timezone(timezone).naive_local().date()
+}
+
+/// The first instant of the given day in the timezone
+///
+/// If midnight does not exist on that day because of a change to daylight saving time, the
+/// earliest existing instant is returned.
+pub(crate) fn start_of_day<Tz: TimeZone>(date: NaiveDate, timezone: &Tz) -> DateTime<Utc> {
+    local_instant(date.and_hms(0, 0, 0), timezone)
+}
+
+/// The instant of the given local time in the timezone
+///
+/// If the local time does not exist because of a change to daylight saving time, the earliest
+/// existing instant after it is returned. If it exists twice, the earlier one is returned.
+pub(crate) fn local_instant<Tz: TimeZone>(local: NaiveDateTime, timezone: &Tz) -> DateTime<Utc> {
+    (0..=24 * 60)
+        .find_map(|minutes| {
+            timezone
+                .from_local_datetime(&(local + Duration::minutes(minutes)))
+                .earliest()
+        })
+        .expect("every day has at least one valid local time")
+        .with_timezone(&Utc)
+}
+
+/// The first midnight in the timezone after the given instant
+pub(crate) fn next_midnight<Tz: TimeZone>(instant: DateTime<Utc>, timezone: &Tz) -> DateTime<Utc> {
+    start_of_day(local_date(instant, timezone).succ(), timezone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedClock;
+    use chrono::{FixedOffset, Timelike};
+
+    fn timezone() -> FixedOffset {
+        FixedOffset::east(2 * 3600)
+    }
+
+    fn local(day: u32, hour: u32) -> DateTime<Utc> {
+        timezone()
+            .ymd(2021, 7, day)
+            .and_hms(hour, 0, 0)
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn split_session_at_midnight() {
+        let session = Session {
+            id: 1,
+            start: local(10, 22),
+            end: Some(local(12, 2)),
+            tags: vec!["test".into()],
+            annotation: None,
+            metadata: Default::default(),
+        };
+        let parts = session
+            .split_at_midnight(&FixedClock(local(20, 0)), &timezone())
+            .collect::<Vec<Session>>();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].start, local(10, 22));
+        assert_eq!(parts[0].end, Some(local(11, 0)));
+        assert_eq!(parts[1].end, Some(local(12, 0)));
+        assert_eq!(parts[2].end, Some(local(12, 2)));
+        assert!(parts.iter().all(|part| part.tags == session.tags));
+    }
+
+    #[test]
+    fn split_open_session_up_to_now() {
+        let start = local(10, 22);
+        let session = Session {
+            id: 1,
+            start,
+            end: None,
+            tags: vec![],
+            annotation: None,
+            metadata: Default::default(),
+        };
+        let parts = session
+            .split_at_midnight(&FixedClock(local(11, 12)), &timezone())
+            .collect::<Vec<Session>>();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].start.with_timezone(&timezone()).hour(), 0);
+        assert_eq!(parts[1].end, None);
+    }
+
+    #[test]
+    fn clip_sessions_to_report_range() {
+        let data = TimewarriorData::from_string(
+            "temp.report.start: 20210705T000000Z\n\
+             temp.report.end: 20210706T000000Z\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210704T230000Z\",\"end\":\"20210705T010000Z\",\"tags\":[]},\n\
+             {\"id\":2,\"start\":\"20210705T230000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210706T010000Z\",\"end\":\"20210706T020000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+        .clipped_to_range(&FixedClock(local(10, 0)))
+        .unwrap();
+        let report_start = data.config.report_start().unwrap().unwrap();
+        let report_end = data.config.report_end().unwrap().unwrap();
+        assert_eq!(data.sessions.len(), 2);
+        assert_eq!(data.sessions[0].start, report_start);
+        assert_eq!(
+            data.sessions[0].duration(&FixedClock(report_end)),
+            Duration::hours(1)
+        );
+        assert_eq!(data.sessions[1].end, Some(report_end));
+    }
+}
@@ -0,0 +1,43 @@
+//! Sources of the current time, used to evaluate sessions which have not ended yet
+
+use chrono::{DateTime, Utc};
+
+/// A source of the current time
+///
+/// Sessions which have not ended yet are treated as running until now, so every duration
+/// computed from them depends on the clock. Use [`SystemClock`] for reports and
+/// [`FixedClock`] for reproducible results.
+pub trait Clock {
+    /// The current point in time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The clock of the operating system
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock which always returns the same point in time
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::{TimeZone, Utc};
+/// use timewarrior_report::{Clock, FixedClock};
+///
+/// let now = Utc.ymd(2021, 7, 11).and_hms(12, 0, 0);
+/// assert_eq!(FixedClock(now).now(), now);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
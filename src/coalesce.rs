@@ -0,0 +1,164 @@
+//! Merging of consecutive sessions with the same tags
+
+use crate::{Session, Tag, TimewarriorData};
+use chrono::Duration;
+
+/// Sessions merged by [`TimewarriorData::coalesce`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coalesced {
+    /// The data with the merged sessions, each keeping the ID of its first source
+    pub data: TimewarriorData,
+    /// The IDs of the original sessions for each of the merged sessions at the same index, in
+    /// the order they were tracked
+    pub sources: Vec<Vec<usize>>,
+}
+
+impl TimewarriorData {
+    /// Merge consecutive sessions with the same tags if at most `max_gap` lies between them
+    ///
+    /// The order of the tags does not matter. A merged session starts with its first and ends
+    /// with its last source and keeps the ID of the first source, the distinct annotations of
+    /// all sources are joined by `; `. A session which has not ended yet is never merged with
+    /// a following one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chrono::Duration;
+    /// use timewarrior_report::TimewarriorData;
+    ///
+    /// let data = TimewarriorData::from_string(
+    ///     "[{\"id\":2,\"start\":\"20210711T100000Z\",\"end\":\"20210711T103000Z\",\"tags\":[\"a\"]},\
+    ///       {\"id\":1,\"start\":\"20210711T103100Z\",\"end\":\"20210711T110000Z\",\"tags\":[\"a\"]}]"
+    ///         .into(),
+    /// )
+    /// .unwrap();
+    /// let coalesced = data.coalesce(Duration::minutes(5));
+    /// assert_eq!(coalesced.data.sessions.len(), 1);
+    /// assert_eq!(coalesced.sources[0], vec![2, 1]);
+    /// ```
+    pub fn coalesce(&self, max_gap: Duration) -> Coalesced {
+        self.coalesce_sessions(max_gap, false)
+    }
+
+    /// Merge consecutive sessions with the same tags and the same annotation if at most
+    /// `max_gap` lies between them, see [`TimewarriorData::coalesce`]
+    pub fn coalesce_with_annotations(&self, max_gap: Duration) -> Coalesced {
+        self.coalesce_sessions(max_gap, true)
+    }
+
+    fn coalesce_sessions(&self, max_gap: Duration, same_annotation: bool) -> Coalesced {
+        let mut sessions = self.sessions.iter().collect::<Vec<&Session>>();
+        sessions.sort_by_key(|session| (session.start, session.id));
+        let mut merged: Vec<(Session, Vec<usize>)> = Vec::new();
+        for session in sessions {
+            if let Some((previous, sources)) = merged.last_mut() {
+                let mergeable = previous
+                    .end
+                    .is_some_and(|end| session.start - end <= max_gap)
+                    && tag_set(&previous.tags) == tag_set(&session.tags)
+                    && (!same_annotation || previous.annotation == session.annotation);
+                if mergeable {
+                    previous.end = match (previous.end, session.end) {
+                        (Some(previous), Some(end)) => Some(previous.max(end)),
+                        _ => None,
+                    };
+                    previous.annotation =
+                        join_annotations(&previous.annotation, &session.annotation);
+                    previous.metadata.extend(session.metadata.clone());
+                    sources.push(session.id);
+                    continue;
+                }
+            }
+            merged.push((session.clone(), vec![session.id]));
+        }
+        let (sessions, sources) = merged.into_iter().unzip();
+        Coalesced {
+            data: TimewarriorData {
+                config: self.config.clone(),
+                sessions,
+            },
+            sources,
+        }
+    }
+}
+
+/// The tags as a sorted set, to compare them regardless of their order
+fn tag_set(tags: &[Tag]) -> Vec<&Tag> {
+    let mut set = tags.iter().collect::<Vec<&Tag>>();
+    set.sort();
+    set.dedup();
+    set
+}
+
+/// Join two annotations, leaving out empty and repeated ones
+fn join_annotations(first: &Option<String>, second: &Option<String>) -> Option<String> {
+    match (first, second) {
+        (Some(first), Some(second))
+            if !second.is_empty() && first.split("; ").all(|part| part != second) =>
+        {
+            Some(format!("{}; {}", first, second))
+        }
+        (None, second) => second.clone(),
+        (first, _) => first.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> TimewarriorData {
+        TimewarriorData::from_string(
+            "[\n\
+             {\"id\":5,\"start\":\"20210711T080000Z\",\"end\":\"20210711T083000Z\",\"tags\":[\"a\",\"b\"],\"annotation\":\"x\"},\n\
+             {\"id\":4,\"start\":\"20210711T083200Z\",\"end\":\"20210711T090000Z\",\"tags\":[\"b\",\"a\"],\"annotation\":\"y\"},\n\
+             {\"id\":3,\"start\":\"20210711T090100Z\",\"end\":\"20210711T091000Z\",\"tags\":[\"a\",\"b\"],\"annotation\":\"y\"},\n\
+             {\"id\":2,\"start\":\"20210711T091000Z\",\"end\":\"20210711T100000Z\",\"tags\":[\"c\"]},\n\
+             {\"id\":1,\"start\":\"20210711T100500Z\",\"tags\":[\"c\"]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_sessions_with_same_tags() {
+        let coalesced = data().coalesce(Duration::minutes(5));
+        assert_eq!(coalesced.sources, vec![vec![5, 4, 3], vec![2, 1]]);
+        let sessions = &coalesced.data.sessions;
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, 5);
+        assert_eq!(sessions[0].end, data().sessions[2].end);
+        assert_eq!(sessions[0].annotation, Some("x; y".into()));
+        assert_eq!(sessions[1].end, None);
+
+        let coalesced = data().coalesce(Duration::minutes(1));
+        assert_eq!(
+            coalesced.sources,
+            vec![vec![5], vec![4, 3], vec![2], vec![1]]
+        );
+    }
+
+    #[test]
+    fn merge_sessions_with_same_annotation() {
+        let coalesced = data().coalesce_with_annotations(Duration::minutes(5));
+        assert_eq!(coalesced.sources, vec![vec![5], vec![4, 3], vec![2, 1]]);
+        assert_eq!(coalesced.data.sessions[1].annotation, Some("y".into()));
+    }
+
+    #[test]
+    fn keep_sources_of_sessions_with_the_same_id() {
+        let data = TimewarriorData::from_string(
+            "[\n\
+             {\"id\":1,\"start\":\"20210710T080000Z\",\"end\":\"20210710T090000Z\",\"tags\":[\"a\"]},\n\
+             {\"id\":1,\"start\":\"20210711T080000Z\",\"end\":\"20210711T090000Z\",\"tags\":[\"a\"]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let coalesced = data.coalesce(Duration::minutes(5));
+        assert_eq!(coalesced.data.sessions.len(), 2);
+        assert_eq!(coalesced.sources, vec![vec![1], vec![1]]);
+    }
+}
@@ -0,0 +1,609 @@
+//! Checks of the tracked time against working time laws like the German Arbeitszeitgesetz
+
+use crate::clip::{local_date, next_midnight};
+use crate::config::{invalid, parse_duration};
+use crate::{Clock, DateRange, ReportConfig, ReportError, Schedule, TimewarriorData};
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Weekday};
+use std::collections::BTreeMap;
+
+/// A single rule on working time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComplianceRule {
+    /// At most this much working time per day
+    MaxDailyTime(Duration),
+    /// Breaks of at least `minimum` in total on days with more than `after` working time
+    MinBreak {
+        /// The working time from which on the breaks are required
+        after: Duration,
+        /// The least time of all breaks of the day
+        minimum: Duration,
+    },
+    /// At least this much rest between the end of work and the start of work on a later day,
+    /// where work belongs to the day it was started on, even if it ends after midnight
+    MinRest(Duration),
+    /// No work on the weekday
+    NoWorkOn(Weekday),
+    /// No work on the holidays of the schedule
+    NoWorkOnHolidays,
+}
+
+impl ComplianceRule {
+    /// A short name of the rule
+    pub fn name(&self) -> String {
+        match self {
+            ComplianceRule::MaxDailyTime(_) => "Daily time".into(),
+            ComplianceRule::MinBreak { .. } => "Breaks".into(),
+            ComplianceRule::MinRest(_) => "Rest".into(),
+            ComplianceRule::NoWorkOn(weekday) => format!("Work on {}", weekday_name(*weekday)),
+            ComplianceRule::NoWorkOnHolidays => "Work on holiday".into(),
+        }
+    }
+
+    /// The limit the rule sets, if it has one
+    pub fn limit(&self) -> Option<Duration> {
+        match self {
+            ComplianceRule::MaxDailyTime(limit) | ComplianceRule::MinRest(limit) => Some(*limit),
+            ComplianceRule::MinBreak { minimum, .. } => Some(*minimum),
+            ComplianceRule::NoWorkOn(_) | ComplianceRule::NoWorkOnHolidays => None,
+        }
+    }
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// A set of rules tracked time is checked against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplianceRules {
+    /// The rules, violations are reported in their order on each day
+    pub rules: Vec<ComplianceRule>,
+    /// The least length of a break to count towards the breaks of a day
+    pub min_break: Duration,
+    /// The schedule with the holidays
+    pub schedule: Schedule,
+}
+
+impl ComplianceRules {
+    /// The rules of the German Arbeitszeitgesetz (ArbZG) for adults
+    ///
+    /// At most 10 hours per day, breaks of 30 minutes after 6 and of 45 minutes after 9 hours
+    /// counted in parts of at least 15 minutes, 11 hours of rest between working days and no
+    /// work on Sundays and holidays.
+    pub fn arbeitszeitgesetz() -> Self {
+        ComplianceRules {
+            rules: vec![
+                ComplianceRule::MaxDailyTime(Duration::hours(10)),
+                ComplianceRule::MinBreak {
+                    after: Duration::hours(6),
+                    minimum: Duration::minutes(30),
+                },
+                ComplianceRule::MinBreak {
+                    after: Duration::hours(9),
+                    minimum: Duration::minutes(45),
+                },
+                ComplianceRule::MinRest(Duration::hours(11)),
+                ComplianceRule::NoWorkOn(Weekday::Sun),
+                ComplianceRule::NoWorkOnHolidays,
+            ],
+            min_break: Duration::minutes(15),
+            schedule: Schedule::default(),
+        }
+    }
+
+    /// The rules of the German Jugendarbeitsschutzgesetz (JArbSchG) for minors
+    ///
+    /// At most 8 hours per day, breaks of 30 minutes after 4.5 and of 60 minutes after 6 hours
+    /// counted in parts of at least 15 minutes, 12 hours of rest between working days and no
+    /// work on Saturdays, Sundays and holidays.
+    pub fn jugendarbeitsschutzgesetz() -> Self {
+        ComplianceRules {
+            rules: vec![
+                ComplianceRule::MaxDailyTime(Duration::hours(8)),
+                ComplianceRule::MinBreak {
+                    after: Duration::minutes(270),
+                    minimum: Duration::minutes(30),
+                },
+                ComplianceRule::MinBreak {
+                    after: Duration::hours(6),
+                    minimum: Duration::minutes(60),
+                },
+                ComplianceRule::MinRest(Duration::hours(12)),
+                ComplianceRule::NoWorkOn(Weekday::Sat),
+                ComplianceRule::NoWorkOn(Weekday::Sun),
+                ComplianceRule::NoWorkOnHolidays,
+            ],
+            min_break: Duration::minutes(15),
+            schedule: Schedule::default(),
+        }
+    }
+
+    /// Read the rules from the `reports.compliance.*` keys of the configuration
+    ///
+    /// `reports.compliance.rules` selects the built-in rule set, `arbzg` (the default),
+    /// `jarbschg` or `none`. Its thresholds are replaced by `reports.compliance.max_daily`,
+    /// `reports.compliance.breaks` like `6h=30min 9h=45min`, `reports.compliance.min_break`,
+    /// `reports.compliance.min_rest` and `reports.compliance.rest_days` like `saturday sunday`,
+    /// where `none` removes the rule. `reports.compliance.holidays` turns the check of work on
+    /// the holidays from `holidays.*` on or off.
+    pub fn from_config(config: &ReportConfig) -> Result<Self, ReportError> {
+        let mut rules = match config.report_setting("compliance", "rules") {
+            None | Some("arbzg") => ComplianceRules::arbeitszeitgesetz(),
+            Some("jarbschg") => ComplianceRules::jugendarbeitsschutzgesetz(),
+            Some("none") => ComplianceRules {
+                rules: Vec::new(),
+                ..ComplianceRules::arbeitszeitgesetz()
+            },
+            Some(value) => {
+                return Err(invalid(
+                    "reports.compliance.rules",
+                    value,
+                    "\"arbzg\", \"jarbschg\" or \"none\"",
+                ))
+            }
+        };
+        rules.schedule = Schedule::from_config(config)?;
+        if let Some(value) = config.report_setting("compliance", "max_daily") {
+            let limit = parse_optional_duration("reports.compliance.max_daily", value)?;
+            rules.replace(
+                |rule| matches!(rule, ComplianceRule::MaxDailyTime(_)),
+                limit
+                    .map(ComplianceRule::MaxDailyTime)
+                    .into_iter()
+                    .collect(),
+            );
+        }
+        if let Some(value) = config.report_setting("compliance", "breaks") {
+            rules.replace(
+                |rule| matches!(rule, ComplianceRule::MinBreak { .. }),
+                parse_breaks(value)?,
+            );
+        }
+        if let Some(min_break) = config.get_duration("reports.compliance.min_break")? {
+            rules.min_break = min_break;
+        }
+        if let Some(value) = config.report_setting("compliance", "min_rest") {
+            let limit = parse_optional_duration("reports.compliance.min_rest", value)?;
+            rules.replace(
+                |rule| matches!(rule, ComplianceRule::MinRest(_)),
+                limit.map(ComplianceRule::MinRest).into_iter().collect(),
+            );
+        }
+        if let Some(value) = config.report_setting("compliance", "rest_days") {
+            rules.replace(
+                |rule| matches!(rule, ComplianceRule::NoWorkOn(_)),
+                parse_weekdays(value)?
+                    .into_iter()
+                    .map(ComplianceRule::NoWorkOn)
+                    .collect(),
+            );
+        }
+        if let Some(holidays) = config.get_bool("reports.compliance.holidays")? {
+            rules.replace(
+                |rule| *rule == ComplianceRule::NoWorkOnHolidays,
+                if holidays {
+                    vec![ComplianceRule::NoWorkOnHolidays]
+                } else {
+                    Vec::new()
+                },
+            );
+        }
+        Ok(rules)
+    }
+
+    /// Replace all rules matching the predicate, inserting the new rules where the first was
+    fn replace(
+        &mut self,
+        predicate: impl Fn(&ComplianceRule) -> bool,
+        replacement: Vec<ComplianceRule>,
+    ) {
+        let position = self
+            .rules
+            .iter()
+            .position(&predicate)
+            .unwrap_or(self.rules.len());
+        self.rules.retain(|rule| !predicate(rule));
+        let position = position.min(self.rules.len());
+        self.rules.splice(position..position, replacement);
+    }
+}
+
+/// A duration, or `None` for `none`
+fn parse_optional_duration(key: &str, value: &str) -> Result<Option<Duration>, ReportError> {
+    if value == "none" {
+        return Ok(None);
+    }
+    parse_duration(value)
+        .map(Some)
+        .ok_or_else(|| invalid(key, value, "a duration or \"none\""))
+}
+
+/// Parse break rules like `6h=30min 9h=45min`, or `none`
+fn parse_breaks(value: &str) -> Result<Vec<ComplianceRule>, ReportError> {
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+    value
+        .split(|character: char| character.is_whitespace() || character == ',')
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            rule.split_once('=')
+                .and_then(|(after, minimum)| {
+                    Some(ComplianceRule::MinBreak {
+                        after: parse_duration(after)?,
+                        minimum: parse_duration(minimum)?,
+                    })
+                })
+                .ok_or_else(|| {
+                    invalid(
+                        "reports.compliance.breaks",
+                        value,
+                        "breaks like 6h=30min 9h=45min or \"none\"",
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Parse weekdays like `saturday sunday`, or `none`
+fn parse_weekdays(value: &str) -> Result<Vec<Weekday>, ReportError> {
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+    value
+        .split(|character: char| character.is_whitespace() || character == ',')
+        .filter(|weekday| !weekday.is_empty())
+        .map(|weekday| {
+            weekday.parse().map_err(|_| {
+                invalid(
+                    "reports.compliance.rest_days",
+                    value,
+                    "weekdays like saturday sunday or \"none\"",
+                )
+            })
+        })
+        .collect()
+}
+
+/// A violation of a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The rule which was violated
+    pub rule: ComplianceRule,
+    /// The day of the violation, for rest the day work started again
+    pub date: NaiveDate,
+    /// IDs of the sessions involved, in the order they were tracked
+    pub sessions: Vec<usize>,
+    /// The working, break or rest time of the violation
+    pub actual: Duration,
+}
+
+/// A stretch of continuous work with the IDs of its sessions
+type Span = (DateRange, Vec<usize>);
+
+impl TimewarriorData {
+    /// All violations of the rules by the sessions within the report range
+    ///
+    /// Days are taken in the given timezone, sessions spanning midnight count towards both
+    /// days. Overlapping sessions are counted once, sessions directly following each other are
+    /// worked without a break. Of the break rules a day violates, only the one requiring the
+    /// longest breaks is reported. Rest is measured from the end of continuous work which
+    /// started on an earlier day to the next start of work, so work past midnight shortens it.
+    /// Violations are sorted by day and by the order of the rules. Sessions which have not
+    /// ended yet are evaluated with the given clock.
+    pub fn compliance_violations<Tz: TimeZone>(
+        &self,
+        rules: &ComplianceRules,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<Vec<Violation>, ReportError> {
+        let now = clock.now();
+        let mut sessions = self
+            .clipped_to_range(clock)?
+            .sessions
+            .iter()
+            .map(|session| {
+                let end = session.end.unwrap_or(now).max(session.start);
+                (DateRange::new(session.start, end), vec![session.id])
+            })
+            .collect::<Vec<Span>>();
+        sessions.sort_by_key(|(range, _)| (range.start, range.end));
+        let spans = merge(sessions.clone());
+
+        let mut days: BTreeMap<NaiveDate, Vec<Span>> = BTreeMap::new();
+        for (range, _) in &spans {
+            let mut start = range.start;
+            while start < range.end {
+                let end = next_midnight(start, timezone).min(range.end);
+                let part = DateRange::new(start, end);
+                let ids = sessions
+                    .iter()
+                    .filter(|(session, _)| session.start < part.end && session.end > part.start)
+                    .flat_map(|(_, ids)| ids.iter().copied())
+                    .collect();
+                days.entry(local_date(start, timezone))
+                    .or_default()
+                    .push((part, ids));
+                start = end;
+            }
+        }
+        let mut rests: BTreeMap<NaiveDate, (Duration, Vec<usize>)> = BTreeMap::new();
+        for pair in spans.windows(2) {
+            let (before, _) = &pair[0];
+            let (after, _) = &pair[1];
+            let date = local_date(after.start, timezone);
+            if date > local_date(before.start, timezone) {
+                let ended = sessions.iter().rfind(|(range, _)| range.end == before.end);
+                let started = sessions
+                    .iter()
+                    .find(|(range, _)| range.start == after.start);
+                let ids = ended
+                    .into_iter()
+                    .chain(started)
+                    .flat_map(|(_, ids)| ids.iter().copied())
+                    .collect();
+                rests.insert(date, (after.start - before.end, ids));
+            }
+        }
+
+        let mut violations = Vec::new();
+        for (date, parts) in &days {
+            let worked = parts.iter().fold(Duration::zero(), |total, (range, _)| {
+                total + range.duration()
+            });
+            let breaks = parts
+                .windows(2)
+                .map(|pair| pair[1].0.start - pair[0].0.end)
+                .filter(|gap| *gap >= rules.min_break)
+                .fold(Duration::zero(), |total, gap| total + gap);
+            let mut ids = parts
+                .iter()
+                .flat_map(|(_, ids)| ids.iter().copied())
+                .collect::<Vec<usize>>();
+            ids.dedup();
+            let mut strictest_break = rules
+                .rules
+                .iter()
+                .filter_map(|rule| match *rule {
+                    ComplianceRule::MinBreak { after, minimum }
+                        if worked > after && breaks < minimum =>
+                    {
+                        Some(minimum)
+                    }
+                    _ => None,
+                })
+                .max();
+            for rule in &rules.rules {
+                let found = match *rule {
+                    ComplianceRule::MaxDailyTime(limit) if worked > limit => Some(worked),
+                    ComplianceRule::MinBreak { after, minimum }
+                        if worked > after && Some(minimum) == strictest_break =>
+                    {
+                        strictest_break = None;
+                        Some(breaks)
+                    }
+                    ComplianceRule::MinRest(limit) => {
+                        if let Some((rest, ids)) = rests.get(date).filter(|(rest, _)| *rest < limit)
+                        {
+                            violations.push(Violation {
+                                rule: *rule,
+                                date: *date,
+                                sessions: ids.clone(),
+                                actual: *rest,
+                            });
+                        }
+                        None
+                    }
+                    ComplianceRule::NoWorkOn(weekday) if date.weekday() == weekday => Some(worked),
+                    ComplianceRule::NoWorkOnHolidays if rules.schedule.holiday(*date).is_some() => {
+                        Some(worked)
+                    }
+                    _ => None,
+                };
+                if let Some(actual) = found {
+                    violations.push(Violation {
+                        rule: *rule,
+                        date: *date,
+                        sessions: ids.clone(),
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+/// Merge overlapping and adjacent sessions, which must be sorted by start
+fn merge(sessions: Vec<Span>) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for (range, ids) in sessions {
+        match spans.last_mut() {
+            Some((last, last_ids)) if range.start <= last.end => {
+                last.end = last.end.max(range.end);
+                last_ids.extend(ids);
+            }
+            _ => spans.push((range, ids)),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FixedClock, ReportTimeZone};
+    use chrono::Utc;
+
+    fn data() -> TimewarriorData {
+        // Friday 11 hours with a 10 minute break, Saturday after 9 hours of rest,
+        // Sunday on a holiday
+        TimewarriorData::from_string(
+            "holidays.de-DE.2021_07_11: Testfeiertag\n\
+             \n\
+             [\n\
+             {\"id\":5,\"start\":\"20210709T070000Z\",\"end\":\"20210709T120000Z\",\"tags\":[]},\n\
+             {\"id\":4,\"start\":\"20210709T121000Z\",\"end\":\"20210709T181000Z\",\"tags\":[]},\n\
+             {\"id\":3,\"start\":\"20210710T031000Z\",\"end\":\"20210710T050000Z\",\"tags\":[]},\n\
+             {\"id\":2,\"start\":\"20210710T043000Z\",\"end\":\"20210710T060000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210711T220000Z\",\"end\":\"20210712T010000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2021, 7, day)
+    }
+
+    #[test]
+    fn find_violations_of_arbeitszeitgesetz() {
+        let data = data();
+        let rules = ComplianceRules::from_config(&data.config).unwrap();
+        let clock = FixedClock(Utc.ymd(2021, 7, 13).and_hms(0, 0, 0));
+        let violations = data
+            .compliance_violations(&rules, &clock, &ReportTimeZone::Utc)
+            .unwrap();
+        let summary = violations
+            .iter()
+            .map(|violation| {
+                (
+                    violation.rule.name(),
+                    violation.date,
+                    violation.sessions.clone(),
+                    violation.actual,
+                )
+            })
+            .collect::<Vec<(String, NaiveDate, Vec<usize>, Duration)>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "Daily time".into(),
+                    date(9),
+                    vec![5, 4],
+                    Duration::hours(11)
+                ),
+                ("Breaks".into(), date(9), vec![5, 4], Duration::zero()),
+                ("Rest".into(), date(10), vec![4, 3], Duration::hours(9)),
+                (
+                    "Work on Sunday".into(),
+                    date(11),
+                    vec![1],
+                    Duration::hours(2)
+                ),
+                (
+                    "Work on holiday".into(),
+                    date(11),
+                    vec![1],
+                    Duration::hours(2)
+                ),
+            ]
+        );
+        assert_eq!(
+            violations[1].rule,
+            ComplianceRule::MinBreak {
+                after: Duration::hours(9),
+                minimum: Duration::minutes(45),
+            }
+        );
+    }
+
+    #[test]
+    fn find_short_rest_after_work_past_midnight() {
+        let data = TimewarriorData::from_string(
+            "[\n\
+             {\"id\":3,\"start\":\"20210712T170000Z\",\"end\":\"20210712T190000Z\",\"tags\":[]},\n\
+             {\"id\":2,\"start\":\"20210712T200000Z\",\"end\":\"20210713T010000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210713T070000Z\",\"end\":\"20210713T120000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let rules = ComplianceRules {
+            rules: vec![ComplianceRule::MinRest(Duration::hours(11))],
+            ..ComplianceRules::arbeitszeitgesetz()
+        };
+        let clock = FixedClock(Utc.ymd(2021, 7, 14).and_hms(0, 0, 0));
+        let violations = data
+            .compliance_violations(&rules, &clock, &ReportTimeZone::Utc)
+            .unwrap();
+        assert_eq!(
+            violations,
+            vec![Violation {
+                rule: ComplianceRule::MinRest(Duration::hours(11)),
+                date: date(13),
+                sessions: vec![2, 1],
+                actual: Duration::hours(6),
+            }]
+        );
+    }
+
+    #[test]
+    fn list_only_sessions_of_the_day() {
+        let data = TimewarriorData::from_string(
+            "[\n\
+             {\"id\":2,\"start\":\"20210712T080000Z\",\"end\":\"20210712T220000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210712T220000Z\",\"end\":\"20210713T110000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let rules = ComplianceRules {
+            rules: vec![ComplianceRule::MaxDailyTime(Duration::hours(10))],
+            ..ComplianceRules::arbeitszeitgesetz()
+        };
+        let clock = FixedClock(Utc.ymd(2021, 7, 14).and_hms(0, 0, 0));
+        let violations = data
+            .compliance_violations(&rules, &clock, &ReportTimeZone::Utc)
+            .unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| (violation.date, violation.sessions.clone()))
+                .collect::<Vec<(NaiveDate, Vec<usize>)>>(),
+            vec![(date(12), vec![2, 1]), (date(13), vec![1])]
+        );
+    }
+
+    #[test]
+    fn override_thresholds_from_config() {
+        let config = [
+            ("reports.compliance.rules", "jarbschg"),
+            ("reports.compliance.max_daily", "none"),
+            ("reports.compliance.breaks", "6h=30min"),
+            ("reports.compliance.min_break", "10min"),
+            ("reports.compliance.rest_days", "sunday"),
+            ("reports.compliance.holidays", "off"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<ReportConfig>();
+        let rules = ComplianceRules::from_config(&config).unwrap();
+        assert_eq!(
+            rules.rules,
+            vec![
+                ComplianceRule::MinBreak {
+                    after: Duration::hours(6),
+                    minimum: Duration::minutes(30),
+                },
+                ComplianceRule::MinRest(Duration::hours(12)),
+                ComplianceRule::NoWorkOn(Weekday::Sun),
+            ]
+        );
+        assert_eq!(rules.min_break, Duration::minutes(10));
+
+        let mut config = ReportConfig::default();
+        config.insert("reports.compliance.breaks".into(), "6h".into());
+        assert!(ComplianceRules::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,545 @@
+//! Typed access to the configuration passed in the report header
+
+use crate::{DateRange, ReportError, ReportTimeZone, TagHierarchy, TIMESTAMP_FORMAT};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+/// The configuration passed to a report by Timewarrior
+///
+/// The raw key/value pairs are kept as they were read and can be accessed through
+/// [`ReportConfig::get`] or [`ReportConfig::raw`]. The typed accessors parse the values on
+/// demand and return a [`ReportError`] if a value can not be interpreted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReportConfig {
+    values: HashMap<String, String>,
+}
+
+impl ReportConfig {
+    /// Create a configuration from a map of raw key/value pairs
+    pub fn new(values: HashMap<String, String>) -> Self {
+        ReportConfig { values }
+    }
+
+    /// Get the raw value of a configuration key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Get the map of all raw key/value pairs
+    pub fn raw(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    /// Consume the configuration and return the map of all raw key/value pairs
+    pub fn into_raw(self) -> HashMap<String, String> {
+        self.values
+    }
+
+    /// Set the raw value of a configuration key, returning the previous value if there was one
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        self.values.insert(key, value)
+    }
+
+    /// Get the value of a key, treating an empty value like a missing one
+    fn get_non_empty(&self, key: &str) -> Option<&str> {
+        self.get(key)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Get the value of a key, returning [`ReportError::MissingConfig`] if it is not set
+    pub fn require(&self, key: &str) -> Result<&str, ReportError> {
+        self.get_non_empty(key)
+            .ok_or_else(|| ReportError::MissingConfig(key.into()))
+    }
+
+    /// Get a boolean value
+    ///
+    /// Timewarrior accepts `on`, `yes`, `y`, `true` and `1` as well as `off`, `no`, `n`,
+    /// `false` and `0`.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, ReportError> {
+        self.get_non_empty(key)
+            .map(|value| match value.to_lowercase().as_str() {
+                "on" | "yes" | "y" | "true" | "1" => Ok(true),
+                "off" | "no" | "n" | "false" | "0" => Ok(false),
+                _ => Err(invalid(key, value, "a boolean")),
+            })
+            .transpose()
+    }
+
+    /// Get an integer value
+    pub fn get_integer(&self, key: &str) -> Result<Option<i64>, ReportError> {
+        self.get_non_empty(key)
+            .map(|value| value.parse().map_err(|_| invalid(key, value, "an integer")))
+            .transpose()
+    }
+
+    /// Get a duration value
+    ///
+    /// Both ISO 8601 durations like `PT1H30M` and the shorthand used by Timewarrior like
+    /// `1h30min`, `90min` or `45s` are accepted, as well as `hh:mm`.
+    pub fn get_duration(&self, key: &str) -> Result<Option<Duration>, ReportError> {
+        self.get_non_empty(key)
+            .map(|value| parse_duration(value).ok_or_else(|| invalid(key, value, "a duration")))
+            .transpose()
+    }
+
+    /// Get a timestamp in the `%Y%m%dT%H%M%SZ` format used by Timewarrior
+    pub fn get_datetime(&self, key: &str) -> Result<Option<DateTime<Utc>>, ReportError> {
+        self.get_non_empty(key)
+            .map(|value| {
+                Utc.datetime_from_str(value, TIMESTAMP_FORMAT)
+                    .map_err(|_| invalid(key, value, "a timestamp"))
+            })
+            .transpose()
+    }
+
+    /// Get a theme color like `bold white on red`
+    pub fn get_color(&self, key: &str) -> Result<Option<Color>, ReportError> {
+        self.get_non_empty(key)
+            .map(|value| value.parse().map_err(|_| invalid(key, value, "a color")))
+            .transpose()
+    }
+
+    /// Start of the report range, taken from `temp.report.start`
+    ///
+    /// `None` if the report is not limited at the start.
+    pub fn report_start(&self) -> Result<Option<DateTime<Utc>>, ReportError> {
+        self.get_datetime("temp.report.start")
+    }
+
+    /// End of the report range, taken from `temp.report.end`
+    ///
+    /// `None` if the report is not limited at the end.
+    pub fn report_end(&self) -> Result<Option<DateTime<Utc>>, ReportError> {
+        self.get_datetime("temp.report.end")
+    }
+
+    /// The report range, if it is limited at both ends
+    pub fn report_range(&self) -> Result<Option<DateRange>, ReportError> {
+        Ok(match (self.report_start()?, self.report_end()?) {
+            (Some(start), Some(end)) => Some(DateRange::new(start, end)),
+            _ => None,
+        })
+    }
+
+    /// Tags the report was filtered by, taken from `temp.report.tags`
+    ///
+    /// Tags containing spaces or commas are quoted by Timewarrior and returned without quotes.
+    pub fn report_tags(&self) -> Vec<String> {
+        let value = match self.get("temp.report.tags") {
+            Some(value) => value,
+            None => return Vec::new(),
+        };
+        let mut tags = Vec::new();
+        let mut current = String::new();
+        let mut quoted = false;
+        for character in value.chars() {
+            match character {
+                '"' => quoted = !quoted,
+                ',' if !quoted => tags.push(std::mem::take(&mut current)),
+                _ => current.push(character),
+            }
+        }
+        tags.push(current);
+        tags.into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    /// Whether color output is enabled, taken from `color` (default: `true`)
+    pub fn color(&self) -> Result<bool, ReportError> {
+        Ok(self.get_bool("color")?.unwrap_or(true))
+    }
+
+    /// Whether verbose output is enabled, taken from `verbose` (default: `true`)
+    pub fn verbose(&self) -> Result<bool, ReportError> {
+        Ok(self.get_bool("verbose")?.unwrap_or(true))
+    }
+
+    /// Get the value of a `reports.<report>.<setting>` key
+    pub fn report_setting(&self, report: &str, setting: &str) -> Option<&str> {
+        self.get_non_empty(&format!("reports.{}.{}", report, setting))
+    }
+
+    /// The timezone a report is rendered in
+    ///
+    /// Taken from `reports.<report>.timezone`, falling back to `reports.timezone` and to the
+    /// local timezone if neither is set.
+    pub fn report_timezone(&self, report: &str) -> Result<ReportTimeZone, ReportError> {
+        let key = format!("reports.{}.timezone", report);
+        let (key, value) = match self.get_non_empty(&key) {
+            Some(value) => (key.as_str(), value),
+            None => match self.get_non_empty("reports.timezone") {
+                Some(value) => ("reports.timezone", value),
+                None => return Ok(ReportTimeZone::Local),
+            },
+        };
+        value
+            .parse()
+            .map_err(|_| invalid(key, value, "a timezone like \"UTC\" or \"+02:00\""))
+    }
+
+    /// The hierarchy of tags, separated into levels by the characters in
+    /// `reports.tag_separators`, or only by `.` if it is not set
+    pub fn tag_hierarchy(&self) -> TagHierarchy {
+        match self.get_non_empty("reports.tag_separators") {
+            Some(separators) => TagHierarchy::new(separators),
+            None => TagHierarchy::default(),
+        }
+    }
+
+    /// The colors of the theme palette, taken from `theme.palette.color01` and following
+    pub fn palette(&self) -> Result<Vec<Color>, ReportError> {
+        let mut palette = Vec::new();
+        for index in 1.. {
+            match self.get_color(&format!("theme.palette.color{:02}", index))? {
+                Some(color) => palette.push(color),
+                None => break,
+            }
+        }
+        Ok(palette)
+    }
+
+    /// A named color of the theme, taken from `theme.colors.<name>`
+    pub fn theme_color(&self, name: &str) -> Result<Option<Color>, ReportError> {
+        self.get_color(&format!("theme.colors.{}", name))
+    }
+}
+
+impl From<HashMap<String, String>> for ReportConfig {
+    fn from(values: HashMap<String, String>) -> Self {
+        ReportConfig::new(values)
+    }
+}
+
+impl FromIterator<(String, String)> for ReportConfig {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        ReportConfig::new(iter.into_iter().collect())
+    }
+}
+
+/// Split a line of the report header into key and value
+///
+/// Only the first `": "` separates key and value, so the value may contain the separator
+/// itself. A line ending in a colon is read as a key with an empty value.
+pub(crate) fn parse_header_line(
+    line_number: usize,
+    line: &str,
+) -> Result<(String, String), ReportError> {
+    let line = line.trim_end();
+    let (key, value) = match line.split_once(": ") {
+        Some((key, value)) => (key, value),
+        None => match line.strip_suffix(':') {
+            Some(key) => (key, ""),
+            None => {
+                return Err(ReportError::Parse {
+                    line: line_number,
+                    message: format!("expected \"<key>: <value>\", found \"{}\"", line),
+                })
+            }
+        },
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(ReportError::Parse {
+            line: line_number,
+            message: format!("missing key in \"{}\"", line),
+        });
+    }
+    Ok((key.into(), value.trim().into()))
+}
+
+pub(crate) fn invalid(key: &str, value: &str, expected: &str) -> ReportError {
+    ReportError::InvalidConfig {
+        key: key.into(),
+        value: value.into(),
+        expected: expected.into(),
+    }
+}
+
+/// Parse a duration in ISO 8601 (`PT1H30M`), Timewarrior shorthand (`1h30min`) or `hh:mm`
+/// notation
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Some((hours, minutes)) = value.split_once(':') {
+        let hours: i64 = hours.parse().ok()?;
+        let minutes: i64 = minutes.parse().ok()?;
+        if minutes >= 60 || hours < 0 || minutes < 0 {
+            return None;
+        }
+        let minutes = hours.checked_mul(60)?.checked_add(minutes)?;
+        return seconds_to_duration(minutes.checked_mul(60)? as f64);
+    }
+    let (iso, rest) = match value
+        .strip_prefix("PT")
+        .or_else(|| value.strip_prefix("pt"))
+    {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let rest = rest.to_lowercase();
+    let mut seconds = 0.0;
+    let mut remaining = rest.as_str();
+    if remaining.is_empty() {
+        return None;
+    }
+    while !remaining.is_empty() {
+        let number_length = remaining
+            .find(|character: char| !(character.is_ascii_digit() || character == '.'))
+            .unwrap_or(remaining.len());
+        let number: f64 = remaining[..number_length].parse().ok()?;
+        remaining = &remaining[number_length..];
+        let unit_length = remaining
+            .find(|character: char| character.is_ascii_digit() || character == '.')
+            .unwrap_or(remaining.len());
+        let factor = match remaining[..unit_length].trim() {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+            "m" if iso => 60.0,
+            "min" | "mins" | "minute" | "minutes" => 60.0,
+            "h" | "hour" | "hours" => 3600.0,
+            "d" | "day" | "days" if !iso => 86400.0,
+            "w" | "week" | "weeks" if !iso => 604_800.0,
+            _ => return None,
+        };
+        seconds += number * factor;
+        remaining = &remaining[unit_length..];
+    }
+    seconds_to_duration(seconds)
+}
+
+/// A duration of the given seconds, `None` if it is out of the range of `Duration`
+fn seconds_to_duration(seconds: f64) -> Option<Duration> {
+    // Duration counts milliseconds in an i64
+    let max = (i64::MAX / 1000) as f64;
+    let seconds = seconds.round();
+    if !seconds.is_finite() || seconds.abs() >= max {
+        return None;
+    }
+    Some(Duration::seconds(seconds as i64))
+}
+
+/// A base color of a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorValue {
+    /// One of the 16 basic colors, where 0 to 7 are the normal and 8 to 15 the bright variants
+    Basic(u8),
+    /// One of the 256 extended colors
+    Extended(u8),
+}
+
+impl ColorValue {
+    fn parse(value: &str) -> Option<Self> {
+        const NAMES: [&str; 8] = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+        if let Some(index) = NAMES.iter().position(|name| *name == value) {
+            return Some(ColorValue::Basic(index as u8));
+        }
+        if let Some(name) = value.strip_prefix("bright ") {
+            let index = NAMES.iter().position(|candidate| *candidate == name)?;
+            return Some(ColorValue::Basic(index as u8 + 8));
+        }
+        if let Some(number) = value.strip_prefix("color") {
+            return number.parse().ok().map(ColorValue::Extended);
+        }
+        if let Some(level) = value
+            .strip_prefix("gray")
+            .or_else(|| value.strip_prefix("grey"))
+        {
+            let level: u8 = level.parse().ok()?;
+            return if level < 24 {
+                Some(ColorValue::Extended(232 + level))
+            } else {
+                None
+            };
+        }
+        if let Some(rgb) = value.strip_prefix("rgb") {
+            let digits = rgb
+                .chars()
+                .map(|digit| digit.to_digit(6).map(|digit| digit as u8))
+                .collect::<Option<Vec<u8>>>()?;
+            return match digits.as_slice() {
+                [red, green, blue] => Some(ColorValue::Extended(16 + red * 36 + green * 6 + blue)),
+                _ => None,
+            };
+        }
+        None
+    }
+
+    fn foreground_code(self) -> String {
+        match self {
+            ColorValue::Basic(index) if index < 8 => format!("{}", 30 + index),
+            ColorValue::Basic(index) => format!("{}", 90 + index - 8),
+            ColorValue::Extended(index) => format!("38;5;{}", index),
+        }
+    }
+
+    fn background_code(self) -> String {
+        match self {
+            ColorValue::Basic(index) if index < 8 => format!("{}", 40 + index),
+            ColorValue::Basic(index) => format!("{}", 100 + index - 8),
+            ColorValue::Extended(index) => format!("48;5;{}", index),
+        }
+    }
+}
+
+/// A color definition of a Timewarrior theme, like `bold white on red`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Color {
+    /// The foreground color, if set
+    pub foreground: Option<ColorValue>,
+    /// The background color, if set
+    pub background: Option<ColorValue>,
+    /// Whether the text is printed in bold
+    pub bold: bool,
+    /// Whether the text is underlined
+    pub underline: bool,
+}
+
+impl Color {
+    /// The ANSI escape sequence to switch to this color
+    pub fn escape_code(&self) -> String {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.underline {
+            codes.push("4".to_string());
+        }
+        if let Some(foreground) = self.foreground {
+            codes.push(foreground.foreground_code());
+        }
+        if let Some(background) = self.background {
+            codes.push(background.background_code());
+        }
+        if codes.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", codes.join(";"))
+        }
+    }
+
+    /// Wrap a text in the escape sequences of this color
+    pub fn paint(&self, text: &str) -> String {
+        let code = self.escape_code();
+        if code.is_empty() {
+            text.into()
+        } else {
+            format!("{}{}\x1b[0m", code, text)
+        }
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        let (foreground, background) = match value.find("on ") {
+            Some(0) => ("", &value[3..]),
+            Some(index) if value[..index].ends_with(' ') => (&value[..index], &value[index + 3..]),
+            _ => (value.as_str(), ""),
+        };
+        let mut color = Color::default();
+        let mut words = foreground.split_whitespace().collect::<Vec<&str>>();
+        words.retain(|word| match *word {
+            "bold" => {
+                color.bold = true;
+                false
+            }
+            "underline" => {
+                color.underline = true;
+                false
+            }
+            _ => true,
+        });
+        if !words.is_empty() {
+            color.foreground = Some(ColorValue::parse(&words.join(" ")).ok_or(())?);
+        }
+        let background = background.split_whitespace().collect::<Vec<&str>>();
+        if !background.is_empty() {
+            color.background = Some(ColorValue::parse(&background.join(" ")).ok_or(())?);
+        }
+        Ok(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(values: &[(&str, &str)]) -> ReportConfig {
+        values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn read_report_range() {
+        let config = config(&[
+            ("temp.report.start", "20210711T000000Z"),
+            ("temp.report.end", ""),
+        ]);
+        assert_eq!(
+            config.report_start().unwrap(),
+            Some(Utc.ymd(2021, 7, 11).and_hms(0, 0, 0))
+        );
+        assert_eq!(config.report_end().unwrap(), None);
+        assert_eq!(config.report_range().unwrap(), None);
+    }
+
+    #[test]
+    fn read_report_tags() {
+        let config = config(&[("temp.report.tags", "\"with space\",plain,\"a,b\"")]);
+        assert_eq!(config.report_tags(), vec!["with space", "plain", "a,b"]);
+    }
+
+    #[test]
+    fn read_typed_values() {
+        let config = config(&[
+            ("color", "off"),
+            ("reports.day.hours", "auto"),
+            ("reports.worktime.monday", "8h"),
+            ("theme.colors.today", "bold white on rgb500"),
+        ]);
+        assert!(!config.color().unwrap());
+        assert!(config.verbose().unwrap());
+        assert_eq!(
+            config.get_duration("reports.worktime.monday").unwrap(),
+            Some(Duration::hours(8))
+        );
+        assert_eq!(
+            config.theme_color("today").unwrap(),
+            Some(Color {
+                foreground: Some(ColorValue::Basic(7)),
+                background: Some(ColorValue::Extended(196)),
+                bold: true,
+                underline: false,
+            })
+        );
+        match config.get_bool("reports.day.hours") {
+            Err(ReportError::InvalidConfig { key, value, .. }) => {
+                assert_eq!(key, "reports.day.hours");
+                assert_eq!(value, "auto");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1h30min"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("7:45"), Some(Duration::minutes(465)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("45s"), Some(Duration::seconds(45)));
+        assert_eq!(parse_duration("abc"), None);
+        assert_eq!(parse_duration("99999999999999999h"), None);
+        assert_eq!(parse_duration("9999999999999999999:00"), None);
+        assert_eq!(parse_duration("999999999999999:00"), None);
+    }
+}
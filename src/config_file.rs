@@ -0,0 +1,217 @@
+//! Parser for the `timewarrior.cfg` configuration file
+
+use crate::{ReportConfig, ReportError};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+impl ReportConfig {
+    /// Read a Timewarrior configuration file like `timewarrior.cfg`
+    ///
+    /// The result contains the same flattened keys Timewarrior passes to reports. Settings are
+    /// written as `key = value`, indented blocks opened by `name:` or `define name:` prefix the
+    /// keys within them, `import <file>` reads another file in place and `#` starts a comment.
+    /// Relative imports are resolved from the directory of the importing file.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use timewarrior_report::ReportConfig;
+    ///
+    /// let config = ReportConfig::from_file("/home/user/.timewarrior/timewarrior.cfg").unwrap();
+    /// println!("{:?}", config.get("reports.day.hours"));
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ReportConfig, ReportError> {
+        let mut config = ReportConfig::default();
+        read_file(path.as_ref(), &mut config, &mut Vec::new())?;
+        Ok(config)
+    }
+}
+
+fn read_file(
+    path: &Path,
+    config: &mut ReportConfig,
+    importing: &mut Vec<PathBuf>,
+) -> Result<(), ReportError> {
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| ReportError::IO(format!("{}: {}", path.display(), e)))?;
+    if importing.contains(&canonical) {
+        return Err(ReportError::Other(format!(
+            "{}: imported recursively",
+            path.display()
+        )));
+    }
+    let contents = fs::read_to_string(&canonical)
+        .map_err(|e| ReportError::IO(format!("{}: {}", path.display(), e)))?;
+    importing.push(canonical);
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let error = |line: usize, message: String| ReportError::Parse {
+        line,
+        message: format!("{}: {}", path.display(), message),
+    };
+
+    // Open blocks as indentation and the prefix they add to the keys within them
+    let mut blocks: Vec<(usize, String)> = Vec::new();
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(raw_line).trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indentation = line.len() - line.trim_start().len();
+        let line = line.trim();
+        while blocks
+            .last()
+            .is_some_and(|(block, _)| *block >= indentation)
+        {
+            blocks.pop();
+        }
+        let prefix = blocks
+            .last()
+            .map(|(_, prefix)| prefix.as_str())
+            .unwrap_or("");
+
+        if let Some(file) = line.strip_prefix("import ") {
+            let file = expand_path(&unquote(file.trim()), directory);
+            read_file(&file, config, importing)?;
+        } else if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(error(line_number, format!("missing key in \"{}\"", line)));
+            }
+            config.insert(format!("{}{}", prefix, key), unquote(value.trim()));
+        } else if let Some(name) = line.strip_suffix(':') {
+            let name = name.strip_prefix("define ").unwrap_or(name).trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(error(line_number, format!("invalid block \"{}\"", line)));
+            }
+            blocks.push((indentation, format!("{}{}.", prefix, name)));
+        } else {
+            return Err(error(
+                line_number,
+                format!(
+                    "expected \"<key> = <value>\" or \"<block>:\", found \"{}\"",
+                    line
+                ),
+            ));
+        }
+    }
+    importing.pop();
+    Ok(())
+}
+
+/// Remove a comment starting with `#` outside of quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// Remove surrounding double quotes from a value
+fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+        .into()
+}
+
+/// Expand a leading `~` and resolve a relative path from the given directory
+fn expand_path(path: &str, directory: &Path) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = env::var_os("HOME") {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    directory.join(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "timewarrior-report-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_config_file_with_blocks_and_imports() {
+        let path = temp_dir("config");
+        fs::write(
+            path.join("timewarrior.cfg"),
+            "# Timewarrior configuration\n\
+             import theme.theme\n\
+             verbose = off   # quiet\n\
+             reports.day.hours = auto\n\
+             \n\
+             define exclusions:\n  \
+               monday = <8:00 12:00-12:45 >17:00\n\
+             define holidays:\n  \
+               de-DE:\n    \
+                 2021_12_25 = \"Christmas #1\"\n  \
+               en-US:\n    \
+                 2021_07_04 = Independence Day\n\
+             reports:\n  \
+               week:\n    \
+                 lines = 20\n",
+        )
+        .unwrap();
+        fs::write(
+            path.join("theme.theme"),
+            "define theme:\n  colors:\n    today = white on red\n",
+        )
+        .unwrap();
+        let config = ReportConfig::from_file(path.join("timewarrior.cfg"));
+        fs::remove_dir_all(&path).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.raw().len(), 7);
+        assert_eq!(config.get("verbose"), Some("off"));
+        assert_eq!(config.get("reports.day.hours"), Some("auto"));
+        assert_eq!(
+            config.get("exclusions.monday"),
+            Some("<8:00 12:00-12:45 >17:00")
+        );
+        assert_eq!(
+            config.get("holidays.de-DE.2021_12_25"),
+            Some("Christmas #1")
+        );
+        assert_eq!(
+            config.get("holidays.en-US.2021_07_04"),
+            Some("Independence Day")
+        );
+        assert_eq!(config.get("reports.week.lines"), Some("20"));
+        assert_eq!(config.get("theme.colors.today"), Some("white on red"));
+    }
+
+    #[test]
+    fn report_syntax_errors_with_file_and_line() {
+        let path = temp_dir("config-error");
+        fs::write(path.join("timewarrior.cfg"), "import other.cfg\n").unwrap();
+        fs::write(path.join("other.cfg"), "color = on\nthis is wrong\n").unwrap();
+        let result = ReportConfig::from_file(path.join("timewarrior.cfg"));
+        fs::write(path.join("other.cfg"), "import timewarrior.cfg\n").unwrap();
+        let recursive = ReportConfig::from_file(path.join("timewarrior.cfg"));
+        fs::remove_dir_all(&path).unwrap();
+        match result {
+            Err(ReportError::Parse { line, message }) => {
+                assert_eq!(line, 2);
+                assert!(message.contains("other.cfg"));
+                assert!(message.contains("this is wrong"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(recursive.is_err());
+    }
+}
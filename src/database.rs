@@ -0,0 +1,320 @@
+//! Direct access to the data files of a Timewarrior database
+
+use crate::{ReportConfig, ReportError, Session, TimewarriorData, TIMESTAMP_FORMAT};
+use chrono::{DateTime, TimeZone, Utc};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// A Timewarrior database on disk
+///
+/// The database is a directory containing the configuration in `timewarrior.cfg` and the
+/// tracked intervals in monthly files like `data/2021-07.data`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use timewarrior_report::TimewarriorDatabase;
+///
+/// let database = TimewarriorDatabase::locate().unwrap();
+/// for session in database.sessions().unwrap() {
+///     println!("@{} {:?}", session.id, session.tags);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimewarriorDatabase {
+    path: PathBuf,
+}
+
+impl TimewarriorDatabase {
+    /// Use the database in the given directory
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        TimewarriorDatabase { path: path.into() }
+    }
+
+    /// Find the database of the current user
+    ///
+    /// Like Timewarrior itself, this uses `TIMEWARRIORDB` if it is set, `~/.timewarrior` if
+    /// it exists and `$XDG_DATA_HOME/timewarrior` otherwise.
+    pub fn locate() -> Result<Self, ReportError> {
+        if let Some(path) = env::var_os("TIMEWARRIORDB") {
+            return Ok(Self::new(path));
+        }
+        let home = env::var_os("HOME")
+            .map(PathBuf::from)
+            .ok_or_else(|| ReportError::Other("Neither TIMEWARRIORDB nor HOME is set".into()))?;
+        let legacy = home.join(".timewarrior");
+        if legacy.is_dir() {
+            return Ok(Self::new(legacy));
+        }
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".local").join("share"));
+        Ok(Self::new(data_home.join("timewarrior")))
+    }
+
+    /// The directory of the database
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All monthly data files of the database, sorted by name
+    pub fn data_files(&self) -> Result<Vec<PathBuf>, ReportError> {
+        let mut files = fs::read_dir(self.path.join("data"))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        files.retain(|file| is_monthly_data_file(file));
+        files.sort();
+        Ok(files)
+    }
+
+    /// Read all sessions of the database
+    ///
+    /// The sessions are sorted by their start and numbered like Timewarrior does, so the most
+    /// recent session has the ID 1.
+    pub fn sessions(&self) -> Result<Vec<Session>, ReportError> {
+        let mut sessions = Vec::new();
+        for file in self.data_files()? {
+            sessions.extend(read_data_file(&file)?);
+        }
+        sessions.sort_by_key(|session| session.start);
+        let count = sessions.len();
+        for (index, session) in sessions.iter_mut().enumerate() {
+            session.id = count - index;
+        }
+        Ok(sessions)
+    }
+
+    /// Read the configuration of the database from `timewarrior.cfg`
+    ///
+    /// An empty configuration is returned if the file does not exist.
+    pub fn config(&self) -> Result<ReportConfig, ReportError> {
+        let path = self.path.join("timewarrior.cfg");
+        if path.is_file() {
+            ReportConfig::from_file(path)
+        } else {
+            Ok(ReportConfig::default())
+        }
+    }
+
+    /// Read the configuration and all sessions of the database into [`TimewarriorData`]
+    pub fn load(&self) -> Result<TimewarriorData, ReportError> {
+        Ok(TimewarriorData {
+            config: self.config()?,
+            sessions: self.sessions()?,
+        })
+    }
+}
+
+/// Whether the file is a monthly data file like `2021-07.data`, other files like `undo.data`
+/// have a different format
+fn is_monthly_data_file(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    let bytes = name.as_bytes();
+    name.len() == 12
+        && name.ends_with(".data")
+        && bytes[4] == b'-'
+        && bytes[..4]
+            .iter()
+            .chain(&bytes[5..7])
+            .all(u8::is_ascii_digit)
+}
+
+/// Read the sessions of a single data file, numbered with 0 as ID
+fn read_data_file(path: &Path) -> Result<Vec<Session>, ReportError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut sessions = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        sessions.push(
+            parse_data_line(&line).map_err(|message| ReportError::Parse {
+                line: index + 1,
+                message: format!("{}: {}", path.display(), message),
+            })?,
+        );
+    }
+    Ok(sessions)
+}
+
+/// Parse a line of a data file, like `inc 20210711T103400Z - 20210711T113400Z # tag # "note"`
+///
+/// The returned session has 0 as ID, as IDs are assigned across the whole database. An
+/// annotation which is not quoted is taken from the rest of the line as it is written.
+fn parse_data_line(line: &str) -> Result<Session, String> {
+    let tokens = tokenize(line)?;
+    let mut tokens = tokens.iter();
+    match tokens.next() {
+        Some((keyword, false, _)) if keyword == "inc" => (),
+        _ => {
+            return Err(format!(
+                "expected a line starting with \"inc\", found \"{}\"",
+                line
+            ))
+        }
+    }
+    let start = match tokens.next() {
+        Some((start, false, _)) => parse_timestamp(start)?,
+        _ => return Err(format!("missing start in \"{}\"", line)),
+    };
+    let mut end = None;
+    let mut next = tokens.next();
+    if let Some((separator, false, _)) = next {
+        if separator == "-" {
+            end = match tokens.next() {
+                Some((end, false, _)) => Some(parse_timestamp(end)?),
+                _ => return Err(format!("missing end in \"{}\"", line)),
+            };
+            next = tokens.next();
+        }
+    }
+    let mut tags = Vec::new();
+    let mut annotation = None;
+    match next {
+        None => (),
+        Some((separator, false, _)) if separator == "#" => {
+            for (token, quoted, end) in tokens.by_ref() {
+                if token == "#" && !quoted {
+                    annotation = Some(match tokens.as_slice() {
+                        [(annotation, true, _)] => annotation.clone(),
+                        _ => line[*end..].trim_start().to_string(),
+                    });
+                    break;
+                }
+                tags.push(token.as_str().into());
+            }
+        }
+        Some((token, _, _)) => return Err(format!("unexpected \"{}\" in \"{}\"", token, line)),
+    }
+    Ok(Session {
+        id: 0,
+        start,
+        end,
+        tags,
+        annotation: annotation.filter(|annotation| !annotation.is_empty()),
+        metadata: Default::default(),
+    })
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, String> {
+    Utc.datetime_from_str(value, TIMESTAMP_FORMAT)
+        .map_err(|_| format!("invalid timestamp \"{}\"", value))
+}
+
+/// Split a line into words and quoted strings, returning whether each token was quoted and
+/// the byte offset at which it ends
+fn tokenize(line: &str) -> Result<Vec<(String, bool, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut characters = line.char_indices().peekable();
+    while let Some(&(_, character)) = characters.peek() {
+        if character.is_whitespace() {
+            characters.next();
+        } else if character == '"' {
+            characters.next();
+            let mut token = String::new();
+            loop {
+                match characters.next() {
+                    Some((index, '"')) => {
+                        tokens.push((token, true, index + 1));
+                        break;
+                    }
+                    Some((_, '\\')) => match characters.next() {
+                        Some((_, 'n')) => token.push('\n'),
+                        Some((_, 't')) => token.push('\t'),
+                        Some((_, escaped)) => token.push(escaped),
+                        None => return Err(format!("unterminated quote in \"{}\"", line)),
+                    },
+                    Some((_, character)) => token.push(character),
+                    None => return Err(format!("unterminated quote in \"{}\"", line)),
+                }
+            }
+        } else {
+            let mut token = String::new();
+            let mut end = line.len();
+            while let Some(&(index, character)) = characters.peek() {
+                if character.is_whitespace() {
+                    end = index;
+                    break;
+                }
+                token.push(character);
+                characters.next();
+            }
+            tokens.push((token, false, end));
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2021, 7, 11).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn parse_data_lines() {
+        let session = parse_data_line(
+            "inc 20210711T103400Z - 20210711T113400Z # acme \"with space\" # \"said \\\"hi\\\"\"",
+        )
+        .unwrap();
+        assert_eq!(session.start, utc(10, 34));
+        assert_eq!(session.end, Some(utc(11, 34)));
+        assert_eq!(session.tags, vec!["acme", "with space"]);
+        assert_eq!(session.annotation, Some("said \"hi\"".into()));
+
+        let session = parse_data_line("inc 20210711T103400Z # \"#\" #").unwrap();
+        assert_eq!(session.end, None);
+        assert_eq!(session.tags, vec!["#"]);
+        assert_eq!(session.annotation, None);
+
+        let session = parse_data_line("inc 20210711T103400Z # a # call  with\tBob \"x\" ").unwrap();
+        assert_eq!(session.tags, vec!["a"]);
+        assert_eq!(session.annotation, Some("call  with\tBob \"x\" ".into()));
+
+        let session = parse_data_line("inc 20210711T103400Z - 20210711T113400Z").unwrap();
+        assert!(session.tags.is_empty());
+        assert!(parse_data_line("exc 20210711T103400Z").is_err());
+        assert!(parse_data_line("inc 20210711T103400Z - ").is_err());
+    }
+
+    #[test]
+    fn read_database_directory() {
+        let path = env::temp_dir().join(format!("timewarrior-report-{}", std::process::id()));
+        fs::create_dir_all(path.join("data")).unwrap();
+        fs::write(
+            path.join("data").join("2021-07.data"),
+            "inc 20210711T103400Z - 20210711T113400Z # a\n\ninc 20210711T120000Z # b\n",
+        )
+        .unwrap();
+        fs::write(
+            path.join("data").join("2021-06.data"),
+            "inc 20210630T080000Z - 20210630T090000Z # c\n",
+        )
+        .unwrap();
+        fs::write(
+            path.join("data").join("undo.data"),
+            "txn:\n  type: interval\n",
+        )
+        .unwrap();
+        fs::write(path.join("data").join("tags.json"), "{}").unwrap();
+        let sessions = TimewarriorDatabase::new(&path).sessions();
+        fs::remove_dir_all(&path).unwrap();
+        let sessions = sessions.unwrap();
+        assert_eq!(
+            sessions
+                .iter()
+                .map(|session| (session.id, session.tags[0].as_str()))
+                .collect::<Vec<(usize, &str)>>(),
+            vec![(3, "c"), (2, "a"), (1, "b")]
+        );
+        assert_eq!(sessions[2].end, None);
+    }
+}
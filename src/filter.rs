@@ -0,0 +1,596 @@
+//! Filtering of sessions by tags, annotations and time
+
+use crate::clip::{local_date, next_midnight, start_of_day};
+use crate::{
+    Clock, MetadataValue, ReportConfig, ReportError, ReportTimeZone, Session, SystemClock,
+    TagHierarchy, TimewarriorData, TIMESTAMP_FORMAT,
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use regex::Regex;
+use std::str::FromStr;
+
+/// A pattern a tag can be matched against
+#[derive(Debug, Clone)]
+pub enum TagPattern {
+    /// A glob pattern, where `*` matches any number and `?` matches exactly one character
+    Glob(String),
+    /// A regular expression, which has to match the whole tag
+    Regex(Regex),
+}
+
+impl TagPattern {
+    /// Create a pattern from a regular expression
+    pub fn regex(pattern: &str) -> Result<Self, ReportError> {
+        Regex::new(&format!("^(?:{})$", pattern))
+            .map(TagPattern::Regex)
+            .map_err(|e| ReportError::InvalidFilter(e.to_string()))
+    }
+
+    /// Whether the given tag matches the pattern
+    pub fn matches(&self, tag: &str) -> bool {
+        match self {
+            TagPattern::Glob(pattern) => glob_matches(pattern, tag),
+            TagPattern::Regex(regex) => regex.is_match(tag),
+        }
+    }
+}
+
+/// Match a glob pattern against a text character by character
+///
+/// On a mismatch after a `*`, the star is retried with one more character, so the time is at
+/// most the product of both lengths.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&expected) if expected == '?' || expected == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&character| character == '*')
+}
+
+/// A composable filter over sessions
+///
+/// Filters can be combined with [`SessionFilter::and`], [`SessionFilter::or`] and
+/// [`SessionFilter::not`], or parsed from an expression in the syntax Timewarrior uses on its
+/// command line, like `tag1 tag2 from 2021-07-01 to 2021-07-08`.
+///
+/// # Example
+///
+/// ```rust
+/// use timewarrior_report::SessionFilter;
+///
+/// let filter = SessionFilter::HasAnyTag(vec!["meeting".into(), "call".into()])
+///     .and(SessionFilter::Closed)
+///     .and(SessionFilter::AnnotationContains("standup".into()).not());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub enum SessionFilter {
+    /// Matches every session
+    #[default]
+    Everything,
+    /// Matches sessions which have all of the tags
+    HasAllTags(Vec<String>),
+    /// Matches sessions which have at least one of the tags
+    HasAnyTag(Vec<String>),
+    /// Matches sessions which have none of the tags
+    HasNoneOfTags(Vec<String>),
+    /// Matches sessions which have at least one tag matching the pattern
+    TagMatches(TagPattern),
+    /// Matches sessions which have the tag or any tag below it in the hierarchy
+    TagUnder(String, TagHierarchy),
+    /// Matches sessions whose annotation contains the text
+    AnnotationContains(String),
+    /// Matches sessions which have the key in their metadata
+    HasMetadata(String),
+    /// Matches sessions which have the key in their metadata with the given value
+    MetadataEquals(String, MetadataValue),
+    /// Matches sessions starting within the bounds, the end bound is exclusive
+    StartsBetween(Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    /// Matches sessions which have ended within the bounds, the end bound is exclusive
+    EndsBetween(Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    /// Matches sessions which overlap with the bounds, the end bound is exclusive
+    Intersects(Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    /// Matches sessions which have not ended yet
+    Open,
+    /// Matches sessions which have ended
+    Closed,
+    /// Matches sessions matching all of the filters
+    All(Vec<SessionFilter>),
+    /// Matches sessions matching at least one of the filters
+    Any(Vec<SessionFilter>),
+    /// Matches sessions not matching the filter
+    Not(Box<SessionFilter>),
+}
+
+impl SessionFilter {
+    /// Combine two filters, so that both have to match
+    pub fn and(self, other: SessionFilter) -> SessionFilter {
+        match self {
+            SessionFilter::Everything => other,
+            SessionFilter::All(mut filters) => {
+                filters.push(other);
+                SessionFilter::All(filters)
+            }
+            filter => SessionFilter::All(vec![filter, other]),
+        }
+    }
+
+    /// Combine two filters, so that at least one of them has to match
+    pub fn or(self, other: SessionFilter) -> SessionFilter {
+        match self {
+            SessionFilter::Any(mut filters) => {
+                filters.push(other);
+                SessionFilter::Any(filters)
+            }
+            filter => SessionFilter::Any(vec![filter, other]),
+        }
+    }
+
+    /// Invert the filter
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> SessionFilter {
+        SessionFilter::Not(Box::new(self))
+    }
+
+    /// Whether the session matches the filter
+    pub fn matches(&self, session: &Session) -> bool {
+        let has_tag = |tag: &String| session.has_tag(tag);
+        match self {
+            SessionFilter::Everything => true,
+            SessionFilter::HasAllTags(tags) => tags.iter().all(has_tag),
+            SessionFilter::HasAnyTag(tags) => tags.iter().any(has_tag),
+            SessionFilter::HasNoneOfTags(tags) => !tags.iter().any(has_tag),
+            SessionFilter::TagMatches(pattern) => {
+                session.tags.iter().any(|tag| pattern.matches(tag))
+            }
+            SessionFilter::TagUnder(ancestor, hierarchy) => {
+                session.has_tag_under(ancestor, hierarchy)
+            }
+            SessionFilter::AnnotationContains(text) => session
+                .annotation
+                .as_ref()
+                .is_some_and(|annotation| annotation.contains(text.as_str())),
+            SessionFilter::HasMetadata(key) => session.metadata.contains_key(key),
+            SessionFilter::MetadataEquals(key, value) => session.metadata.get(key) == Some(value),
+            SessionFilter::StartsBetween(from, to) => within(session.start, *from, *to),
+            SessionFilter::EndsBetween(from, to) => {
+                session.end.is_some_and(|end| within(end, *from, *to))
+            }
+            SessionFilter::Intersects(from, to) => {
+                to.is_none_or(|to| session.start < to)
+                    && from.is_none_or(|from| session.end.is_none_or(|end| end > from))
+            }
+            SessionFilter::Open => session.end.is_none(),
+            SessionFilter::Closed => session.end.is_some(),
+            SessionFilter::All(filters) => filters.iter().all(|filter| filter.matches(session)),
+            SessionFilter::Any(filters) => filters.iter().any(|filter| filter.matches(session)),
+            SessionFilter::Not(filter) => !filter.matches(session),
+        }
+    }
+
+    /// Parse a filter expression in the syntax of the Timewarrior command line
+    ///
+    /// Plain words are tags which all have to be present, tags containing spaces can be
+    /// quoted. Quoted words are always tags, even if they look like keywords or dates. A time
+    /// range can be given as `from <date>`, `since <date>`, `to <date>`, `before <date>`,
+    /// `<date> - <date>`, `<date> for <duration>` or as one of the hints `:day`,
+    /// `:yesterday`, `:week` and `:month`. Dates are written as `2021-07-11`,
+    /// `2021-07-11T10:34`, `10:34`, `20210711T103400Z`, `now`, `today`, `yesterday` or
+    /// `tomorrow`. Relative dates are resolved with the given clock and dates without an
+    /// explicit offset are taken in the given timezone. Sessions overlapping with the range
+    /// match the filter.
+    pub fn parse<Tz: TimeZone>(
+        expression: &str,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<SessionFilter, ReportError> {
+        let now = clock.now();
+        let mut tags = Vec::new();
+        let mut from = None;
+        let mut to = None;
+        let mut tokens = tokenize(expression)?.into_iter();
+        while let Some((token, quoted)) = tokens.next() {
+            if quoted {
+                tags.push(token);
+                continue;
+            }
+            let mut argument = |keyword: &str| {
+                tokens.next().map(|(value, _)| value).ok_or_else(|| {
+                    ReportError::InvalidFilter(format!("missing value after \"{}\"", keyword))
+                })
+            };
+            match token.as_str() {
+                "from" | "since" | "after" => {
+                    from = Some(parse_date(&argument(&token)?, now, timezone)?)
+                }
+                "to" | "until" | "before" | "-" => {
+                    to = Some(parse_date(&argument(&token)?, now, timezone)?)
+                }
+                "for" => {
+                    let value = argument(&token)?;
+                    let duration = crate::config::parse_duration(&value).ok_or_else(|| {
+                        ReportError::InvalidFilter(format!("invalid duration \"{}\"", value))
+                    })?;
+                    let start = from.ok_or_else(|| {
+                        ReportError::InvalidFilter("\"for\" needs a start date".into())
+                    })?;
+                    to = Some(start + duration);
+                }
+                hint if hint.starts_with(':') => {
+                    let (start, end) = parse_hint(hint, now, timezone)?;
+                    from = Some(start);
+                    to = Some(end);
+                }
+                _ if from.is_none() && to.is_none() && is_date(&token, now, timezone) => {
+                    from = Some(parse_date(&token, now, timezone)?)
+                }
+                _ => tags.push(token),
+            }
+        }
+        let mut filter = SessionFilter::Everything;
+        if !tags.is_empty() {
+            filter = filter.and(SessionFilter::HasAllTags(tags));
+        }
+        if from.is_some() || to.is_some() {
+            filter = filter.and(SessionFilter::Intersects(from, to));
+        }
+        Ok(filter)
+    }
+}
+
+impl FromStr for SessionFilter {
+    type Err = ReportError;
+
+    /// Parse a filter expression with the system clock in the local timezone
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        SessionFilter::parse(expression, &SystemClock, &ReportTimeZone::Local)
+    }
+}
+
+fn within(instant: DateTime<Utc>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> bool {
+    from.is_none_or(|from| instant >= from) && to.is_none_or(|to| instant < to)
+}
+
+/// Split an expression into words and quoted strings, returning whether each token was quoted
+fn tokenize(expression: &str) -> Result<Vec<(String, bool)>, ReportError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<(String, bool)> = None;
+    let mut quoted = false;
+    for character in expression.chars() {
+        match character {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(Default::default).1 = true;
+            }
+            _ if character.is_whitespace() && !quoted => {
+                if let Some(token) = current.take() {
+                    tokens.push(token);
+                }
+            }
+            _ => current
+                .get_or_insert_with(Default::default)
+                .0
+                .push(character),
+        }
+    }
+    if quoted {
+        return Err(ReportError::InvalidFilter(format!(
+            "unterminated quote in \"{}\"",
+            expression
+        )));
+    }
+    tokens.extend(current);
+    Ok(tokens)
+}
+
+fn is_date<Tz: TimeZone>(token: &str, now: DateTime<Utc>, timezone: &Tz) -> bool {
+    token.starts_with(|character: char| character.is_ascii_digit())
+        && parse_date(token, now, timezone).is_ok()
+}
+
+fn local<Tz: TimeZone>(naive: NaiveDateTime, timezone: &Tz) -> Option<DateTime<Utc>> {
+    timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Parse a date as it is written on the Timewarrior command line
+pub(crate) fn parse_date<Tz: TimeZone>(
+    value: &str,
+    now: DateTime<Utc>,
+    timezone: &Tz,
+) -> Result<DateTime<Utc>, ReportError> {
+    let today = local_date(now, timezone);
+    let date = match value {
+        "now" => Some(now),
+        "today" => Some(start_of_day(today, timezone)),
+        "yesterday" => Some(start_of_day(today.pred(), timezone)),
+        "tomorrow" => Some(start_of_day(today.succ(), timezone)),
+        _ => Utc
+            .datetime_from_str(value, TIMESTAMP_FORMAT)
+            .ok()
+            .or_else(|| {
+                ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+                    .iter()
+                    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                    .and_then(|naive| local(naive, timezone))
+            })
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .ok()
+                    .map(|date| start_of_day(date, timezone))
+            })
+            .or_else(|| {
+                ["%H:%M:%S", "%H:%M"]
+                    .iter()
+                    .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+                    .and_then(|time| local(today.and_time(time), timezone))
+            }),
+    };
+    date.ok_or_else(|| ReportError::InvalidFilter(format!("invalid date \"{}\"", value)))
+}
+
+fn parse_hint<Tz: TimeZone>(
+    hint: &str,
+    now: DateTime<Utc>,
+    timezone: &Tz,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), ReportError> {
+    let today = local_date(now, timezone);
+    let range = |start: NaiveDate, end: NaiveDate| {
+        Ok((start_of_day(start, timezone), start_of_day(end, timezone)))
+    };
+    match hint {
+        ":day" | ":today" => Ok((start_of_day(today, timezone), next_midnight(now, timezone))),
+        ":yesterday" => range(today.pred(), today),
+        ":week" => {
+            let monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+            range(monday, monday + Duration::weeks(1))
+        }
+        ":month" => {
+            let first = today.with_day(1).expect("every month has a first day");
+            let next = if first.month() == 12 {
+                NaiveDate::from_ymd(first.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd(first.year(), first.month() + 1, 1)
+            };
+            range(first, next)
+        }
+        _ => Err(ReportError::InvalidFilter(format!(
+            "unknown hint \"{}\"",
+            hint
+        ))),
+    }
+}
+
+/// A borrowed view of the sessions of [`TimewarriorData`] matching a filter
+#[derive(Debug, Clone)]
+pub struct SessionView<'a> {
+    /// The configuration passed to the report
+    pub config: &'a ReportConfig,
+    /// The sessions matching the filter
+    pub sessions: Vec<&'a Session>,
+}
+
+impl<'a> SessionView<'a> {
+    /// Narrow the view down further
+    pub fn filter(&self, filter: &SessionFilter) -> SessionView<'a> {
+        SessionView {
+            config: self.config,
+            sessions: self
+                .sessions
+                .iter()
+                .copied()
+                .filter(|session| filter.matches(session))
+                .collect(),
+        }
+    }
+
+    /// Copy the sessions in the view into a new [`TimewarriorData`]
+    pub fn to_data(&self) -> TimewarriorData {
+        TimewarriorData {
+            config: self.config.clone(),
+            sessions: self
+                .sessions
+                .iter()
+                .map(|session| (*session).clone())
+                .collect(),
+        }
+    }
+}
+
+impl TimewarriorData {
+    /// A view of all sessions matching the filter
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chrono::{TimeZone, Utc};
+    /// use timewarrior_report::{FixedClock, ReportTimeZone, SessionFilter, TimewarriorData};
+    ///
+    /// let report_data = TimewarriorData::from_string(
+    ///     "test: test\n\n[{\"id\":1,\"start\":\"20210711T103400Z\",\"tags\":[\"a\"]}]".into(),
+    /// )
+    /// .unwrap();
+    /// let clock = FixedClock(Utc.ymd(2021, 7, 12).and_hms(0, 0, 0));
+    /// let filter =
+    ///     SessionFilter::parse("a from 2021-07-01", &clock, &ReportTimeZone::Utc).unwrap();
+    /// assert_eq!(report_data.filter(&filter).sessions.len(), 1);
+    /// ```
+    pub fn filter(&self, filter: &SessionFilter) -> SessionView<'_> {
+        SessionView {
+            config: &self.config,
+            sessions: self
+                .sessions
+                .iter()
+                .filter(|session| filter.matches(session))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tag;
+
+    fn session(id: usize, tags: &[&str], annotation: Option<&str>, open: bool) -> Session {
+        let start = Utc.ymd(2021, 7, 11).and_hms(10, id as u32, 0);
+        Session {
+            id,
+            start,
+            end: if open {
+                None
+            } else {
+                Some(start + Duration::minutes(30))
+            },
+            tags: tags.iter().map(|tag| Tag::from(*tag)).collect(),
+            annotation: annotation.map(String::from),
+            metadata: Default::default(),
+        }
+    }
+
+    fn data() -> TimewarriorData {
+        TimewarriorData {
+            config: ReportConfig::default(),
+            sessions: vec![
+                session(1, &["acme", "meeting"], Some("weekly sync"), false),
+                session(2, &["acme.web", "code"], None, false),
+                session(3, &["internal"], Some("hiring"), true),
+            ],
+        }
+    }
+
+    fn ids(view: &SessionView) -> Vec<usize> {
+        view.sessions.iter().map(|session| session.id).collect()
+    }
+
+    #[test]
+    fn filter_by_tags() {
+        let data = data();
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect();
+        assert_eq!(
+            ids(&data.filter(&SessionFilter::HasAllTags(tags(&["acme", "meeting"])))),
+            vec![1]
+        );
+        assert_eq!(
+            ids(&data.filter(&SessionFilter::HasAnyTag(tags(&["code", "internal"])))),
+            vec![2, 3]
+        );
+        assert_eq!(
+            ids(&data.filter(&SessionFilter::HasNoneOfTags(tags(&["acme"])))),
+            vec![2, 3]
+        );
+        assert_eq!(
+            ids(&data.filter(&SessionFilter::TagMatches(TagPattern::Glob("acme*".into())))),
+            vec![1, 2]
+        );
+        assert_eq!(
+            ids(&data.filter(&SessionFilter::TagMatches(
+                TagPattern::regex(r"acme\.\w+").unwrap()
+            ))),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn match_glob_patterns_by_character() {
+        let glob = |pattern: &str| TagPattern::Glob(pattern.into());
+        assert!(glob("caf?").matches("café"));
+        assert!(glob("*é*").matches("café crème"));
+        assert!(!glob("caf??").matches("café"));
+        assert!(glob("a*b*c").matches("aXbYbZc"));
+        assert!(!glob("a*b*c").matches("aXbYbZ"));
+        assert!(glob("*").matches(""));
+        assert!(!glob("?").matches(""));
+        assert!(!glob(&"*a".repeat(20)).matches(&format!("{}b", "a".repeat(40))));
+    }
+
+    #[test]
+    fn combine_filters() {
+        let data = data();
+        let filter = SessionFilter::AnnotationContains("sync".into())
+            .and(SessionFilter::Closed)
+            .or(SessionFilter::TagMatches(TagPattern::Glob("*.web".into())));
+        assert_eq!(ids(&data.filter(&filter)), vec![1, 2]);
+        assert_eq!(ids(&data.filter(&filter.not())), vec![3]);
+        assert_eq!(ids(&data.filter(&SessionFilter::Open)), vec![3]);
+    }
+
+    fn parse(expression: &str) -> Result<SessionFilter, ReportError> {
+        let clock = crate::FixedClock(Utc.ymd(2021, 7, 11).and_hms(22, 30, 0));
+        SessionFilter::parse(expression, &clock, &ReportTimeZone::Utc)
+    }
+
+    #[test]
+    fn parse_filter_expression() {
+        let data = data();
+        let filter = parse("acme \"meeting\" from 2021-07-11T10:00 to 2021-07-11T10:20").unwrap();
+        assert_eq!(ids(&data.filter(&filter)), vec![1]);
+        let filter = parse("2021-07-11T10:31 - 2021-07-11T11:00").unwrap();
+        assert_eq!(ids(&data.filter(&filter)), vec![2, 3]);
+        let filter = parse("2021-07-11T10:31 for 1min").unwrap();
+        assert_eq!(ids(&data.filter(&filter)), vec![2, 3]);
+        assert!(parse("from").is_err());
+        assert!(parse("\"unterminated").is_err());
+        assert!(parse(":fortnight").is_err());
+    }
+
+    #[test]
+    fn read_quoted_keywords_and_dates_as_tags() {
+        let filter = parse("\"from\" \"2021-07-11\" \"-\" since 2021-07-11").unwrap();
+        match filter {
+            SessionFilter::All(filters) => {
+                assert!(matches!(
+                    &filters[0],
+                    SessionFilter::HasAllTags(tags) if tags == &["from", "2021-07-11", "-"]
+                ));
+                assert!(matches!(
+                    filters[1],
+                    SessionFilter::Intersects(Some(_), None)
+                ));
+            }
+            filter => panic!("unexpected filter {:?}", filter),
+        }
+    }
+
+    #[test]
+    fn resolve_dates_in_timezone() {
+        let now = Utc.ymd(2021, 7, 11).and_hms(22, 30, 0);
+        let timezone: ReportTimeZone = "+02:00".parse().unwrap();
+        assert_eq!(
+            parse_date("today", now, &timezone).unwrap(),
+            Utc.ymd(2021, 7, 11).and_hms(22, 0, 0)
+        );
+        assert_eq!(
+            parse_date("2021-07-11T10:00", now, &timezone).unwrap(),
+            Utc.ymd(2021, 7, 11).and_hms(8, 0, 0)
+        );
+        assert_eq!(
+            parse_hint(":day", now, &ReportTimeZone::Utc).unwrap(),
+            (
+                Utc.ymd(2021, 7, 11).and_hms(0, 0, 0),
+                Utc.ymd(2021, 7, 12).and_hms(0, 0, 0)
+            )
+        );
+    }
+}
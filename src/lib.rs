@@ -1,9 +1,48 @@
 use chrono::prelude::*;
-use serde::Deserialize;
+use chrono::Duration;
+use serde::{Deserialize, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
+
+mod aggregate;
+mod billing;
+mod clip;
+mod clock;
+mod coalesce;
+mod compliance;
+pub mod config;
+mod config_file;
+mod database;
+mod filter;
+mod metadata;
+pub mod reports;
+mod rounding;
+mod schedule;
+mod tag;
+mod timezone;
+mod validate;
+mod worktime;
+
+pub use billing::{BillingRules, Invoice, InvoiceTotal, LineItem, Money, Percentage};
+pub use clip::SplitAtMidnight;
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use coalesce::Coalesced;
+pub use compliance::{ComplianceRule, ComplianceRules, Violation};
+pub use config::ReportConfig;
+pub use database::TimewarriorDatabase;
+pub use filter::{SessionFilter, SessionView, TagPattern};
+pub use metadata::{parse_metadata, MetadataValue};
+pub use rounding::{Rounding, RoundingMode, RoundingScope};
+pub use schedule::Schedule;
+pub use tag::{Tag, TagHierarchy, TagNode};
+pub use timezone::ReportTimeZone;
+pub use validate::{Gap, Overlap};
+pub use worktime::{DayBalance, WorkTime};
+
+/// The format Timewarrior uses for timestamps, which are always in UTC
+pub(crate) const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
 
 /// An enum to represent errors occurring while processing report data from Timewarrior
 #[derive(Debug)]
@@ -12,6 +51,26 @@ pub enum ReportError {
     IO(String),
     /// An error, which occurred while deserializing or serializing a session from JSON
     SerdeJson(String),
+    /// A configuration value, which is required but not set
+    MissingConfig(String),
+    /// A configuration value, which could not be interpreted
+    InvalidConfig {
+        /// The configuration key
+        key: String,
+        /// The raw value of the key
+        value: String,
+        /// A description of the expected value
+        expected: String,
+    },
+    /// A filter expression or pattern, which could not be parsed
+    InvalidFilter(String),
+    /// A malformed line in the report input
+    Parse {
+        /// Number of the line, starting at 1
+        line: usize,
+        /// A description of the problem
+        message: String,
+    },
     /// Some other error
     Other(String),
 }
@@ -23,6 +82,20 @@ impl fmt::Display for ReportError {
         match self {
             ReportError::IO(e) => write!(f, "IOError: {}", e),
             ReportError::SerdeJson(e) => write!(f, "SerdeJsonError: {}", e),
+            ReportError::MissingConfig(key) => write!(f, "Missing config value: {}", key),
+            ReportError::InvalidConfig {
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid config value: {} is \"{}\", expected {}",
+                key, value, expected
+            ),
+            ReportError::InvalidFilter(e) => write!(f, "Invalid filter: {}", e),
+            ReportError::Parse { line, message } => {
+                write!(f, "ParseError in line {}: {}", line, message)
+            }
             ReportError::Other(e) => write!(f, "Other Error: {}", e),
         }
     }
@@ -41,47 +114,91 @@ impl From<serde_json::Error> for ReportError {
 }
 
 mod my_date_format {
-    use chrono::{DateTime, Local, TimeZone, Utc};
-    use serde::{self, Deserialize, Deserializer};
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &str = "%Y%m%dT%H%M%SZ";
+    const FORMAT: &str = crate::TIMESTAMP_FORMAT;
+
+    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&date.format(FORMAT))
+    }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Ok(Utc
-            .datetime_from_str(&s, FORMAT)
-            .map_err(serde::de::Error::custom)?
-            .with_timezone(&Local))
+        Utc.datetime_from_str(&s, FORMAT)
+            .map_err(serde::de::Error::custom)
     }
 }
 
 mod my_optional_date_format {
-    use chrono::{DateTime, Local, TimeZone, Utc};
-    use serde::{self, Deserialize, Deserializer};
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &str = "%Y%m%dT%H%M%SZ";
+    const FORMAT: &str = crate::TIMESTAMP_FORMAT;
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Local>>, D::Error>
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => super::my_date_format::serialize(date, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
         Ok(Some(
             Utc.datetime_from_str(&s, FORMAT)
-                .map_err(serde::de::Error::custom)?
-                .with_timezone(&Local),
+                .map_err(serde::de::Error::custom)?,
         ))
     }
 }
 
+/// A range of time between two points in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DateRange {
+    /// Start of the range
+    pub start: DateTime<Utc>,
+    /// End of the range, not included in the range itself
+    pub end: DateTime<Utc>,
+}
+
+impl DateRange {
+    /// Create a new range from start to end
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        DateRange { start, end }
+    }
+
+    /// Whether the given point in time lies within the range
+    pub fn contains(&self, instant: DateTime<Utc>) -> bool {
+        self.start <= instant && instant < self.end
+    }
+
+    /// Duration of the range, zero if it ends before it starts
+    pub fn duration(&self) -> Duration {
+        if self.end > self.start {
+            self.end - self.start
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
 /// A representation of the data within the report
-#[derive(Debug, Eq)]
+#[derive(Debug, Clone, Eq)]
 pub struct TimewarriorData {
     /// The configurations passed to the report
-    pub config: HashMap<String, String>,
+    pub config: ReportConfig,
     /// A vector of all tracked sessions within the report
     pub sessions: Vec<Session>,
 }
@@ -92,16 +209,25 @@ impl PartialEq for TimewarriorData {
     }
 }
 
+/// The data is serialized as a JSON array of its sessions, the format used by `timew export`
+/// and `timew import`. Use [`TimewarriorData::write_report`] to include the configuration.
+impl Serialize for TimewarriorData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.sessions.serialize(serializer)
+    }
+}
+
 impl TimewarriorData {
     /// Read the report from standard input
     ///
     /// This should be the usual way to read the report data.
     pub fn from_stdin() -> Result<Self, ReportError> {
-        let mut input_string = String::new();
-        for line in io::stdin().lock().lines() {
-            input_string = format!("{}\n{}", input_string, line?);
-        }
-        Self::from_string(input_string.trim().into())
+        let stdin = io::stdin();
+        let data = Self::from_reader(stdin.lock())?;
+        Ok(data)
     }
 
     /// Read the report from a given string
@@ -124,35 +250,163 @@ impl TimewarriorData {
     /// );
     /// ```
     pub fn from_string(input: String) -> Result<Self, ReportError> {
-        let input_vec = &input.split("\n\n").collect::<Vec<&str>>();
-        let mut config = HashMap::new();
-        for line in input_vec[0].lines() {
-            let setting = line.split(": ").collect::<Vec<&str>>();
-            config.insert(setting[0].into(), setting[1].into());
+        Self::from_reader(input.as_bytes())
+    }
+
+    /// Serialize the sessions to JSON in the format used by `timew export` and `timew import`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use timewarrior_report::TimewarriorData;
+    ///
+    /// let input = "[{\"id\":1,\"start\":\"20210711T103400Z\",\"tags\":[\"test\"]}]";
+    /// let report_data = TimewarriorData::from_string(format!("test: test\n\n{}", input)).unwrap();
+    /// assert_eq!(report_data.to_export_json().unwrap(), input);
+    /// ```
+    pub fn to_export_json(&self) -> Result<String, ReportError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Write the data in the format Timewarrior passes to a report
+    ///
+    /// The configuration is written as a header sorted by key, followed by a blank line and the
+    /// sessions as JSON, so the output can be read again with [`TimewarriorData::from_reader`].
+    pub fn write_report<W: Write>(&self, mut writer: W) -> Result<(), ReportError> {
+        let mut settings = self.config.raw().iter().collect::<Vec<_>>();
+        settings.sort();
+        for (key, value) in settings {
+            writeln!(writer, "{}: {}", key, value)?;
+        }
+        writeln!(writer)?;
+        serde_json::to_writer(&mut writer, self)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    /// Read plain `timew export` output, a JSON array of sessions without configuration
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use timewarrior_report::TimewarriorData;
+    ///
+    /// let report_data = TimewarriorData::from_export_json(
+    ///     "[{\"id\":1,\"start\":\"20210711T103400Z\",\"end\":\"20210711T113400Z\"}]",
+    /// )
+    /// .unwrap();
+    /// assert!(report_data.config.raw().is_empty());
+    /// assert!(report_data.sessions[0].tags.is_empty());
+    /// ```
+    pub fn from_export_json(input: &str) -> Result<Self, ReportError> {
+        Ok(TimewarriorData {
+            config: ReportConfig::default(),
+            sessions: Session::from_reader(input.as_bytes())?,
+        })
+    }
+
+    /// Read the report from a buffered reader
+    ///
+    /// The configuration header is read line by line up to the first blank line, the sessions
+    /// following it are deserialized directly from the reader without buffering the whole
+    /// input. Input starting with a JSON array instead of a header, like the output of
+    /// `timew export`, is read as sessions without configuration.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::io::Cursor;
+    /// use timewarrior_report::TimewarriorData;
+    ///
+    /// let input = Cursor::new("verbose: on\n\n[{\"id\":1,\"start\":\"20210711T103400Z\",\"tags\":[]}]");
+    /// let report_data = TimewarriorData::from_reader(input).unwrap();
+    /// assert_eq!(report_data.config.get("verbose"), Some("on"));
+    /// assert_eq!(report_data.sessions.len(), 1);
+    /// ```
+    pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Self, ReportError> {
+        let mut config = ReportConfig::default();
+        let mut line = String::new();
+        let mut line_number = skip_whitespace(&mut reader)?;
+        if reader.fill_buf()?.first() == Some(&b'[') {
+            return Ok(TimewarriorData {
+                config,
+                sessions: Session::from_reader(reader)?,
+            });
+        }
+        loop {
+            line.clear();
+            line_number += 1;
+            if reader.read_line(&mut line)? == 0 {
+                return Err(ReportError::Parse {
+                    line: line_number,
+                    message: "unexpected end of input, expected a blank line after the \
+                              configuration header"
+                        .into(),
+                });
+            }
+            let setting = line.trim_end();
+            if setting.is_empty() {
+                break;
+            }
+            let (key, value) = config::parse_header_line(line_number, setting)?;
+            config.insert(key, value);
         }
         Ok(TimewarriorData {
             config,
-            sessions: Session::from_json(&input_vec[1])?,
+            sessions: Session::from_reader(reader)?,
         })
     }
 }
+
+/// Skip whitespace at the start of the reader, returning the number of skipped lines
+fn skip_whitespace<R: BufRead>(reader: &mut R) -> Result<usize, ReportError> {
+    let mut lines = 0;
+    loop {
+        let buffer = reader.fill_buf()?;
+        let whitespace = buffer
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        lines += buffer[..whitespace]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count();
+        let done = whitespace < buffer.len() || buffer.is_empty();
+        reader.consume(whitespace);
+        if done {
+            return Ok(lines);
+        }
+    }
+}
+
 /// A tracked session from Timewarrior
-#[derive(Debug, Deserialize, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq)]
 pub struct Session {
     /// ID of the session within Timewarrior
     pub id: usize,
-    /// Start time of the session
+    /// Start time of the session in UTC
+    ///
+    /// Use [`DateTime::with_timezone`] to convert it into the timezone of a report.
     #[serde(with = "my_date_format")]
-    pub start: DateTime<Local>,
-    /// End time of the session. `Some(DateTime<Local>)` if it did end, `None` otherwise.
+    pub start: DateTime<Utc>,
+    /// End time of the session. `Some(DateTime<Utc>)` if it did end, `None` otherwise.
     #[serde(default)]
     #[serde(with = "my_optional_date_format")]
-    pub end: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
     /// Tags attached to the session
-    pub tags: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
     /// Annotation of the session. `Some(String)` if the session has an annotation, `None`
     /// otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub annotation: Option<String>,
+    /// Structured metadata from the annotation, like `ticket=ACME-42` or `#billable`
+    ///
+    /// Empty unless the annotation was parsed with [`Session::parse_metadata`] or
+    /// [`TimewarriorData::with_metadata`].
+    #[serde(skip)]
+    pub metadata: HashMap<String, MetadataValue>,
 }
 
 impl PartialEq for Session {
@@ -173,17 +427,44 @@ impl Ord for Session {
 
 impl PartialOrd for Session {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
 impl Session {
-    fn from_json(data: &str) -> Result<Vec<Session>, ReportError> {
-        Ok(serde_json::from_str::<Vec<Session>>(data)?)
+    /// Duration of the session
+    ///
+    /// A session which has not ended yet is treated as running until the current time of the
+    /// clock. If that lies before the start of such a session, the duration is zero.
+    pub fn duration(&self, clock: &dyn Clock) -> Duration {
+        let end = self.end.unwrap_or_else(|| clock.now());
+        if end > self.start {
+            end - self.start
+        } else {
+            Duration::zero()
+        }
+    }
+
+    /// Whether the session has the tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+
+    /// Whether the session has the tag or any tag below it in the hierarchy
+    pub fn has_tag_under(&self, ancestor: &str, hierarchy: &TagHierarchy) -> bool {
+        self.tags
+            .iter()
+            .any(|tag| tag.is_under(ancestor, hierarchy))
+    }
+
+    fn from_reader<R: io::Read>(reader: R) -> Result<Vec<Session>, ReportError> {
+        Ok(serde_json::from_reader::<_, Vec<Session>>(reader)?)
     }
 }
 
 #[cfg(test)]
+// The dates of the original tests are written with leading zeros
+#[allow(clippy::zero_prefixed_literal)]
 mod tests {
     use super::*;
 
@@ -202,6 +483,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_timewarrior_data_from_reader() {
+        let input = "temp.report.start: 20210711T000000Z\ntemp.report.tags: \nverbose: on\n\n[\n{\"id\":2,\"start\":\"20210711T103400Z\",\"end\":\"20210711T113400Z\",\"tags\":[\"test\"]},\n{\"id\":1,\"start\":\"20210711T113400Z\",\"tags\":[]}\n]\n";
+        let report_data =
+            TimewarriorData::from_reader(io::BufReader::new(input.as_bytes())).unwrap();
+        assert_eq!(report_data.config.raw().len(), 3);
+        assert_eq!(report_data.config.get("temp.report.tags"), Some(""));
+        assert_eq!(
+            report_data
+                .sessions
+                .iter()
+                .map(|session| session.id)
+                .collect::<Vec<usize>>(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn read_config_header_with_crlf_and_separator_in_value() {
+        let input = "temp.report.tags: \r\nfoo: bar: baz  \r\n\r\n[]";
+        let report_data = TimewarriorData::from_string(input.into()).unwrap();
+        assert_eq!(report_data.config.get("temp.report.tags"), Some(""));
+        assert_eq!(report_data.config.get("foo"), Some("bar: baz"));
+    }
+
+    #[test]
+    fn report_malformed_config_header() {
+        match TimewarriorData::from_string("foo: bar\nbaz\n\n[]".into()) {
+            Err(ReportError::Parse { line, message }) => {
+                assert_eq!(line, 2);
+                assert!(message.contains("baz"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match TimewarriorData::from_string("foo: bar\n".into()) {
+            Err(ReportError::Parse { line, .. }) => assert_eq!(line, 2),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn compute_session_duration() {
+        let start = Utc.ymd(2021, 7, 11).and_hms(10, 34, 0);
+        let mut session = Session {
+            id: 1,
+            start,
+            end: Some(start + Duration::minutes(90)),
+            tags: vec![],
+            annotation: None,
+            metadata: Default::default(),
+        };
+        assert_eq!(session.duration(&FixedClock(start)), Duration::minutes(90));
+        session.end = None;
+        assert_eq!(
+            session.duration(&FixedClock(start + Duration::minutes(30))),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            session.duration(&FixedClock(start - Duration::minutes(30))),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn serialize_sessions_to_timewarrior_json() {
+        let input = "[{\"id\":2,\"start\":\"20210711T103400Z\",\"end\":\"20210711T113400Z\",\"tags\":[\"a\",\"b\"],\"annotation\":\"with \\\"quotes\\\"\"},{\"id\":1,\"start\":\"20210711T113400Z\",\"tags\":[]}]";
+        let report_data = TimewarriorData::from_string(format!("foo: bar\n\n{}", input)).unwrap();
+        assert_eq!(report_data.to_export_json().unwrap(), input);
+
+        let mut output = Vec::new();
+        report_data.write_report(&mut output).unwrap();
+        assert_eq!(
+            TimewarriorData::from_reader(output.as_slice()).unwrap(),
+            report_data
+        );
+    }
+
+    #[test]
+    fn read_export_without_header() {
+        let input = "\n  [\n{\"id\":2,\"start\":\"20210711T103400Z\",\"end\":\"20210711T113400Z\"},\n{\"id\":1,\"start\":\"20210711T113400Z\",\"tags\":[\"a\"]}\n]\n";
+        let report_data = TimewarriorData::from_string(input.into()).unwrap();
+        assert_eq!(
+            report_data,
+            TimewarriorData::from_export_json(input).unwrap()
+        );
+        assert!(report_data.config.raw().is_empty());
+        assert_eq!(report_data.sessions.len(), 2);
+        assert!(report_data.sessions[0].tags.is_empty());
+    }
+
     #[test]
     fn create_session_without_minial_data() {
         let test_session = serde_json::from_str::<Session>(
@@ -215,11 +586,11 @@ mod tests {
                 start: DateTime::<Utc>::from_utc(
                     NaiveDate::from_ymd(2021, 07, 11).and_hms(10, 34, 00),
                     Utc
-                )
-                .with_timezone(&Local),
+                ),
                 end: None,
                 tags: vec![],
                 annotation: None,
+                metadata: Default::default(),
             }
         );
     }
@@ -237,11 +608,11 @@ mod tests {
                 start: DateTime::<Utc>::from_utc(
                     NaiveDate::from_ymd(2021, 07, 11).and_hms(10, 34, 00),
                     Utc
-                )
-                .with_timezone(&Local),
+                ),
                 end: None,
-                tags: vec!["test".to_string()],
+                tags: vec!["test".into()],
                 annotation: Some("this is a test".to_string()),
+                metadata: Default::default(),
             }
         );
     }
@@ -259,17 +630,14 @@ mod tests {
                 start: DateTime::<Utc>::from_utc(
                     NaiveDate::from_ymd(2021, 07, 11).and_hms(10, 34, 00),
                     Utc
-                )
-                .with_timezone(&Local),
-                end: Some(
-                    DateTime::<Utc>::from_utc(
-                        NaiveDate::from_ymd(2021, 07, 11).and_hms(11, 34, 00),
-                        Utc
-                    )
-                    .with_timezone(&Local)
                 ),
-                tags: vec!["test".to_string()],
+                end: Some(DateTime::<Utc>::from_utc(
+                    NaiveDate::from_ymd(2021, 07, 11).and_hms(11, 34, 00),
+                    Utc
+                )),
+                tags: vec!["test".into()],
                 annotation: Some("this is a test".to_string()),
+                metadata: Default::default(),
             }
         );
     }
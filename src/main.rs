@@ -1,3 +1,71 @@
+use std::env;
+use std::path::Path;
+use std::process;
+use timewarrior_report::{reports, ReportError, SystemClock, TimewarriorData};
+
+/// The reports this extension can produce
+const MODES: [&str; 9] = [
+    "summary",
+    "csv",
+    "tags",
+    "tag_summary",
+    "validate",
+    "invoice",
+    "worktime",
+    "compliance",
+    "week",
+];
+
+/// Determine the report to produce
+///
+/// The report is taken from the first argument, or from the name of the executable, so the
+/// extension can be linked into the Timewarrior extension directory under the name of a
+/// report, e.g. as `csv`. Without either, the summary is produced.
+fn mode() -> String {
+    let mut args = env::args();
+    let executable = args.next().unwrap_or_default();
+    if let Some(mode) = args.next() {
+        return mode;
+    }
+    let name = Path::new(&executable)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    MODES
+        .iter()
+        .find(|mode| **mode == name)
+        .unwrap_or(&"summary")
+        .to_string()
+}
+
+fn run(mode: &str) -> Result<String, ReportError> {
+    if !MODES.contains(&mode) {
+        return Err(ReportError::Other(format!(
+            "Unknown report \"{}\", expected one of: {}",
+            mode,
+            MODES.join(", ")
+        )));
+    }
+    let data = TimewarriorData::from_stdin()?;
+    match mode {
+        "csv" => reports::csv::render(&data, &SystemClock),
+        "tags" => reports::tag_tree::render(&data, &SystemClock),
+        "tag_summary" => reports::tag_summary::render(&data, &SystemClock),
+        "validate" => reports::validate::render(&data, &SystemClock),
+        "invoice" => reports::invoice::render(&data, &SystemClock),
+        "worktime" => reports::worktime::render(&data, &SystemClock),
+        "compliance" => reports::compliance::render(&data, &SystemClock),
+        "week" => reports::week::render(&data, &SystemClock),
+        _ => reports::day_summary::render(&data, &SystemClock),
+    }
+}
+
 fn main() {
-    dbg!(timewarrior_report::TimewarriorData::from_stdin().unwrap());
+    match run(&mode()) {
+        Ok(output) => print!("{}", output),
+        Err(error) => {
+            eprintln!("timewarrior_report: {}", error);
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,208 @@
+//! Structured metadata within the annotations of sessions, like `ticket=ACME-42 #billable`
+
+use crate::{Session, TimewarriorData};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A value of the metadata within an annotation
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MetadataValue {
+    /// `true` or `false` written without quotes, or a `#hashtag`, which is always `true`
+    Bool(bool),
+    /// A whole number written without quotes
+    Integer(i64),
+    /// Any other value, or a value written in double quotes
+    Text(String),
+}
+
+impl MetadataValue {
+    fn parse_unquoted(value: &str) -> Self {
+        match value {
+            "true" => MetadataValue::Bool(true),
+            "false" => MetadataValue::Bool(false),
+            _ => value
+                .parse()
+                .map(MetadataValue::Integer)
+                .unwrap_or_else(|_| MetadataValue::Text(value.into())),
+        }
+    }
+}
+
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataValue::Bool(value) => write!(f, "{}", value),
+            MetadataValue::Integer(value) => write!(f, "{}", value),
+            MetadataValue::Text(value) => f.write_str(value),
+        }
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::Text(value.into())
+    }
+}
+
+impl From<i64> for MetadataValue {
+    fn from(value: i64) -> Self {
+        MetadataValue::Integer(value)
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        MetadataValue::Bool(value)
+    }
+}
+
+/// Whether the character may be part of a key or a hashtag
+fn is_key_character(character: char) -> bool {
+    character.is_alphanumeric() || matches!(character, '_' | '-' | '.')
+}
+
+/// Parse the metadata within an annotation
+///
+/// Fields are written as `key=value` anywhere within the annotation, values containing spaces
+/// can be written in double quotes, where `\"` stands for a quote. A `#hashtag` sets the key
+/// `hashtag` to `true`. All other words of the annotation are ignored. If a key occurs more
+/// than once, the last value wins.
+///
+/// # Example
+///
+/// ```rust
+/// use timewarrior_report::{parse_metadata, MetadataValue};
+///
+/// let metadata = parse_metadata("Fixed login ticket=ACME-42 hours=3 customer=\"Acme Inc\" #billable");
+/// assert_eq!(metadata["ticket"], MetadataValue::Text("ACME-42".into()));
+/// assert_eq!(metadata["hours"], MetadataValue::Integer(3));
+/// assert_eq!(metadata["customer"], MetadataValue::Text("Acme Inc".into()));
+/// assert_eq!(metadata["billable"], MetadataValue::Bool(true));
+/// ```
+pub fn parse_metadata(annotation: &str) -> HashMap<String, MetadataValue> {
+    let mut metadata = HashMap::new();
+    let mut characters = annotation.chars().peekable();
+    while let Some(&character) = characters.peek() {
+        if character.is_whitespace() {
+            characters.next();
+            continue;
+        }
+        let mut word = String::new();
+        while let Some(&character) = characters.peek() {
+            if character.is_whitespace() || (character == '"' && word.ends_with('=')) {
+                break;
+            }
+            word.push(character);
+            characters.next();
+        }
+        if let Some(hashtag) = word.strip_prefix('#') {
+            let hashtag = hashtag.trim_end_matches(|character: char| !is_key_character(character));
+            if !hashtag.is_empty() && hashtag.chars().all(is_key_character) {
+                metadata.insert(hashtag.into(), MetadataValue::Bool(true));
+            }
+            continue;
+        }
+        let (key, value) = match word.split_once('=') {
+            Some((key, value)) if !key.is_empty() && key.chars().all(is_key_character) => {
+                (key.to_string(), value.to_string())
+            }
+            _ => continue,
+        };
+        if value.is_empty() && characters.peek() == Some(&'"') {
+            characters.next();
+            let mut value = String::new();
+            while let Some(character) = characters.next() {
+                match character {
+                    '"' => break,
+                    '\\' if characters.peek() == Some(&'"') => {
+                        value.push('"');
+                        characters.next();
+                    }
+                    _ => value.push(character),
+                }
+            }
+            metadata.insert(key, MetadataValue::Text(value));
+        } else if !value.is_empty() {
+            metadata.insert(key, MetadataValue::parse_unquoted(&value));
+        }
+    }
+    metadata
+}
+
+impl Session {
+    /// Fill [`Session::metadata`] from the annotation, see [`parse_metadata`]
+    pub fn parse_metadata(&mut self) {
+        self.metadata = self
+            .annotation
+            .as_deref()
+            .map(parse_metadata)
+            .unwrap_or_default();
+    }
+}
+
+impl TimewarriorData {
+    /// Fill the metadata of all sessions from their annotations, see [`parse_metadata`]
+    ///
+    /// Parsing the annotations is opt-in, without calling this the metadata of all sessions is
+    /// empty and filters or groupings by metadata do not match anything.
+    pub fn with_metadata(mut self) -> Self {
+        self.sessions.iter_mut().for_each(Session::parse_metadata);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SessionFilter, SystemClock};
+    use chrono::Duration;
+
+    #[test]
+    fn parse_fields_and_hashtags() {
+        let metadata = parse_metadata(
+            "Call with ticket=ACME-42 billable=false note=\"said \\\"hi\\\" twice\" #urgent! #b=c x=",
+        );
+        assert_eq!(metadata.len(), 4);
+        assert_eq!(metadata["ticket"], "ACME-42".into());
+        assert_eq!(metadata["billable"], false.into());
+        assert_eq!(metadata["note"], "said \"hi\" twice".into());
+        assert_eq!(metadata["urgent"], true.into());
+        assert!(parse_metadata("no metadata = here").is_empty());
+    }
+
+    #[test]
+    fn filter_and_group_by_metadata() {
+        let data = TimewarriorData::from_string(
+            "temp.report.start: 20210711T000000Z\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210711T080000Z\",\"end\":\"20210711T090000Z\",\"annotation\":\"customer=acme #billable\"},\n\
+             {\"id\":2,\"start\":\"20210711T090000Z\",\"end\":\"20210711T120000Z\",\"annotation\":\"customer=acme\"},\n\
+             {\"id\":1,\"start\":\"20210711T120000Z\",\"end\":\"20210711T130000Z\",\"annotation\":\"customer=\\\"Globex\\\" #billable\"}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        assert!(data
+            .duration_by_metadata("customer", &SystemClock)
+            .unwrap()
+            .is_empty());
+
+        let data = data.with_metadata();
+        let durations = data.duration_by_metadata("customer", &SystemClock).unwrap();
+        assert_eq!(durations[&"acme".into()], Duration::hours(4));
+        assert_eq!(durations[&"Globex".into()], Duration::hours(1));
+        let billable = data.filter(&SessionFilter::MetadataEquals(
+            "billable".into(),
+            true.into(),
+        ));
+        assert_eq!(
+            billable
+                .sessions
+                .iter()
+                .map(|session| session.id)
+                .collect::<Vec<usize>>(),
+            vec![3, 1]
+        );
+    }
+}
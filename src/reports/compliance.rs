@@ -0,0 +1,93 @@
+//! A list of violations of working time laws like the German Arbeitszeitgesetz
+
+use super::{format_duration, Alignment, Table};
+use crate::{Clock, ComplianceRules, ReportError, TimewarriorData};
+
+/// Render all violations of the working time rules within the report range as a table
+///
+/// The rules are read as described in [`ComplianceRules::from_config`]. Days are taken in the
+/// timezone from `reports.compliance.timezone` or `reports.timezone`. The actual time is the
+/// working time of the day, the time of all breaks or the rest before the day, depending on
+/// the rule.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let config = &data.config;
+    let rules = ComplianceRules::from_config(config)?;
+    let timezone = config.report_timezone("compliance")?;
+    let violations = data.compliance_violations(&rules, clock, &timezone)?;
+    if violations.is_empty() {
+        return Ok(if config.verbose()? {
+            "No violations found.\n".into()
+        } else {
+            String::new()
+        });
+    }
+
+    let mut table = Table::new(&[
+        ("Date", Alignment::Left),
+        ("Day", Alignment::Left),
+        ("Rule", Alignment::Left),
+        ("IDs", Alignment::Left),
+        ("Actual", Alignment::Right),
+        ("Limit", Alignment::Right),
+    ]);
+    for violation in &violations {
+        table.add_row(
+            vec![
+                violation.date.format("%Y-%m-%d").to_string(),
+                violation.date.format("%a").to_string(),
+                violation.rule.name(),
+                violation
+                    .sessions
+                    .iter()
+                    .map(|id| format!("@{}", id))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                format_duration(violation.actual),
+                violation
+                    .rule
+                    .limit()
+                    .map(format_duration)
+                    .unwrap_or_default(),
+            ],
+            None,
+        );
+    }
+    Ok(table.render(config.verbose()?, config.color()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedClock;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn render_violations() {
+        let data = TimewarriorData::from_string(
+            "color: off\n\
+             reports.timezone: utc\n\
+             reports.compliance.max_daily: 8h\n\
+             \n\
+             [\n\
+             {\"id\":2,\"start\":\"20210710T080000Z\",\"end\":\"20210710T170000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210711T080000Z\",\"end\":\"20210711T100000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let clock = FixedClock(Utc.ymd(2021, 7, 12).and_hms(0, 0, 0));
+        assert_eq!(
+            render(&data, &clock)
+                .unwrap()
+                .lines()
+                .collect::<Vec<&str>>(),
+            vec![
+                "Date       Day Rule           IDs  Actual   Limit",
+                "---------- --- -------------- --- ------- -------",
+                "2021-07-10 Sat Daily time     @2  9:00:00 8:00:00",
+                "2021-07-10 Sat Breaks         @2  0:00:00 0:30:00",
+                "2021-07-11 Sun Work on Sunday @1  2:00:00",
+            ]
+        );
+    }
+}
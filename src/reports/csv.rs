@@ -0,0 +1,251 @@
+//! Export of sessions as comma separated values, e.g. for timesheets
+
+use super::format_duration;
+use crate::{Clock, ReportError, ReportTimeZone, Session, TimewarriorData};
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+/// Format of the start and end times
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How the tags of a session are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagColumns {
+    /// All tags in a single column, joined by the given separator
+    Joined(String),
+    /// One column per tag, as many columns as the session with the most tags needs
+    Separate,
+}
+
+/// Options for writing sessions as CSV
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// The character separating the fields of a row
+    pub delimiter: char,
+    /// How the tags of a session are written
+    pub tags: TagColumns,
+    /// Whether a header row with the column names is written
+    pub header: bool,
+    /// The timezone start and end times are written in
+    pub timezone: ReportTimeZone,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            tags: TagColumns::Joined(" ".into()),
+            header: true,
+            timezone: ReportTimeZone::Local,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Read the options from the `reports.csv.*` keys of the report configuration
+    ///
+    /// `reports.csv.delimiter` sets the field delimiter, `reports.csv.tag_separator` the
+    /// separator of joined tags, `reports.csv.tag_columns` writes one column per tag,
+    /// `reports.csv.header` controls the header row and `reports.csv.timezone` or
+    /// `reports.timezone` the timezone of the times.
+    pub fn from_config(data: &TimewarriorData) -> Result<Self, ReportError> {
+        let config = &data.config;
+        let mut options = CsvOptions::default();
+        if let Some(delimiter) = config.get("reports.csv.delimiter") {
+            let mut characters = delimiter.chars();
+            options.delimiter = match (characters.next(), characters.next()) {
+                (Some(character), None) => character,
+                _ => {
+                    return Err(ReportError::InvalidConfig {
+                        key: "reports.csv.delimiter".into(),
+                        value: delimiter.into(),
+                        expected: "a single character".into(),
+                    })
+                }
+            };
+        }
+        if config.get_bool("reports.csv.tag_columns")?.unwrap_or(false) {
+            options.tags = TagColumns::Separate;
+        } else if let Some(separator) = config.get("reports.csv.tag_separator") {
+            options.tags = TagColumns::Joined(separator.into());
+        }
+        options.header = config.get_bool("reports.csv.header")?.unwrap_or(true);
+        options.timezone = config.report_timezone("csv")?;
+        Ok(options)
+    }
+}
+
+/// Quote a field if it contains the delimiter, a quote or a line break
+fn quote(field: &str, delimiter: char) -> String {
+    if field.contains(|character| {
+        character == delimiter || character == '"' || character == '\n' || character == '\r'
+    }) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+fn format_date(date: DateTime<Utc>, timezone: &ReportTimeZone) -> String {
+    date.with_timezone(timezone).format(DATE_FORMAT).to_string()
+}
+
+/// Write one row per session with ID, start and end, duration, tags and annotation
+///
+/// The end of a session which has not ended yet is left empty, its duration is counted up
+/// to the current time of the clock.
+///
+/// # Example
+///
+/// ```rust
+/// use timewarrior_report::reports::csv::{write, CsvOptions};
+/// use timewarrior_report::{SystemClock, TimewarriorData};
+///
+/// let report_data = TimewarriorData::from_string(
+///     "test: test\n\n[{\"id\":1,\"start\":\"20210711T103400Z\",\"end\":\"20210711T113400Z\",\"tags\":[\"a\"],\"annotation\":\"a, b\"}]".into(),
+/// )
+/// .unwrap();
+/// let mut output = Vec::new();
+/// write(&report_data, &CsvOptions::default(), &SystemClock, &mut output).unwrap();
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.ends_with(",1:00:00,a,\"a, b\"\n"));
+/// ```
+pub fn write<W: Write>(
+    data: &TimewarriorData,
+    options: &CsvOptions,
+    clock: &dyn Clock,
+    mut writer: W,
+) -> Result<(), ReportError> {
+    let tag_columns = match options.tags {
+        TagColumns::Separate => data
+            .sessions
+            .iter()
+            .map(|session| session.tags.len())
+            .max()
+            .unwrap_or(0),
+        TagColumns::Joined(_) => 1,
+    };
+    let mut write_row = |fields: Vec<String>| {
+        let row = fields
+            .iter()
+            .map(|field| quote(field, options.delimiter))
+            .collect::<Vec<String>>()
+            .join(&options.delimiter.to_string());
+        writeln!(writer, "{}", row)
+    };
+    if options.header {
+        let mut fields = vec!["id".into(), "start".into(), "end".into(), "duration".into()];
+        match options.tags {
+            TagColumns::Separate => {
+                fields.extend((1..=tag_columns).map(|index| format!("tag{}", index)))
+            }
+            TagColumns::Joined(_) => fields.push("tags".into()),
+        }
+        fields.push("annotation".into());
+        write_row(fields)?;
+    }
+    for session in &data.sessions {
+        write_row(session_fields(session, options, tag_columns, clock))?;
+    }
+    Ok(())
+}
+
+fn session_fields(
+    session: &Session,
+    options: &CsvOptions,
+    tag_columns: usize,
+    clock: &dyn Clock,
+) -> Vec<String> {
+    let mut fields = vec![
+        session.id.to_string(),
+        format_date(session.start, &options.timezone),
+        session
+            .end
+            .map(|end| format_date(end, &options.timezone))
+            .unwrap_or_default(),
+        format_duration(session.duration(clock)),
+    ];
+    match &options.tags {
+        TagColumns::Separate => {
+            fields.extend(session.tags.iter().map(|tag| tag.to_string()));
+            fields.resize(4 + tag_columns, String::new());
+        }
+        TagColumns::Joined(separator) => fields.push(session.tags.join(separator)),
+    }
+    fields.push(session.annotation.clone().unwrap_or_default());
+    fields
+}
+
+/// Render all sessions as CSV with the options from the report configuration
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let mut output = Vec::new();
+    write(data, &CsvOptions::from_config(data)?, clock, &mut output)?;
+    String::from_utf8(output).map_err(|e| ReportError::Other(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SystemClock;
+    use chrono::TimeZone;
+
+    fn data(config: &[(&str, &str)]) -> TimewarriorData {
+        let start = Utc.ymd(2021, 7, 11).and_hms(8, 0, 0);
+        TimewarriorData {
+            config: config
+                .iter()
+                .chain(&[("reports.timezone", "+02:00")])
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            sessions: vec![
+                Session {
+                    id: 2,
+                    start,
+                    end: Some(Utc.ymd(2021, 7, 11).and_hms(9, 30, 0)),
+                    tags: vec!["acme".into(), "web, api".into()],
+                    annotation: Some("said \"hi\"\nand left".into()),
+                    metadata: Default::default(),
+                },
+                Session {
+                    id: 1,
+                    start,
+                    end: Some(start),
+                    tags: vec![],
+                    annotation: None,
+                    metadata: Default::default(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn write_joined_tags_with_quoting() {
+        let output = render(&data(&[("reports.csv.tag_separator", "|")]), &SystemClock).unwrap();
+        assert_eq!(
+            output,
+            "id,start,end,duration,tags,annotation\n\
+             2,2021-07-11 10:00:00,2021-07-11 11:30:00,1:30:00,\"acme|web, api\",\"said \"\"hi\"\"\nand left\"\n\
+             1,2021-07-11 10:00:00,2021-07-11 10:00:00,0:00:00,,\n"
+        );
+    }
+
+    #[test]
+    fn write_one_column_per_tag() {
+        let output = render(
+            &data(&[
+                ("reports.csv.tag_columns", "yes"),
+                ("reports.csv.delimiter", ";"),
+                ("reports.csv.header", "off"),
+            ]),
+            &SystemClock,
+        )
+        .unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert!(lines[0]
+            .starts_with("2;2021-07-11 10:00:00;2021-07-11 11:30:00;1:30:00;acme;web, api;"));
+        assert_eq!(
+            lines[2],
+            "1;2021-07-11 10:00:00;2021-07-11 10:00:00;0:00:00;;;"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! A summary of all sessions grouped by day, similar to `timew summary`
+
+use super::{format_duration, Alignment, Table};
+use crate::clip::local_date;
+use crate::config::Color;
+use crate::{Clock, ReportError, Session, TimewarriorData};
+use chrono::{Datelike, Duration};
+
+/// Render a table of all sessions within the report range grouped by day
+///
+/// Sessions are clipped to the report range and split at midnight. Each day ends with its
+/// total, the table ends with the grand total of all days. The `color` and `verbose` settings
+/// of the configuration control whether the output is colored and whether a header is printed.
+/// Sessions which have not ended yet are evaluated with the given clock. Days and times are
+/// shown in the timezone from `reports.summary.timezone` or `reports.timezone`.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let color = data.config.color()?;
+    let verbose = data.config.verbose()?;
+    let timezone = data.config.report_timezone("summary")?;
+    let mut parts = data
+        .clipped_to_range(clock)?
+        .sessions
+        .iter()
+        .flat_map(|session| session.split_at_midnight(clock, &timezone))
+        .collect::<Vec<Session>>();
+    parts.sort_by_key(|part| part.start);
+    if parts.is_empty() {
+        return Ok(if verbose {
+            "No filtered data found.\n".into()
+        } else {
+            String::new()
+        });
+    }
+
+    let mut table = Table::new(&[
+        ("Wk", Alignment::Left),
+        ("Date", Alignment::Left),
+        ("Day", Alignment::Left),
+        ("ID", Alignment::Right),
+        ("Tags", Alignment::Left),
+        ("Start", Alignment::Right),
+        ("End", Alignment::Right),
+        ("Time", Alignment::Right),
+        ("Total", Alignment::Right),
+    ]);
+    let mut total = Duration::zero();
+    let mut day_total = Duration::zero();
+    let mut previous: Option<&Session> = None;
+    let date_of = |session: &Session| local_date(session.start, &timezone);
+    for (index, part) in parts.iter().enumerate() {
+        let date = date_of(part);
+        let new_day = previous.is_none_or(|previous| date_of(previous) != date);
+        let new_week =
+            previous.is_none_or(|previous| date_of(previous).iso_week() != date.iso_week());
+        let duration = part.duration(clock);
+        day_total = day_total + duration;
+        total = total + duration;
+        let last_of_day = parts
+            .get(index + 1)
+            .is_none_or(|next| date_of(next) != date);
+        table.add_row(
+            vec![
+                if new_week {
+                    format!("W{}", date.iso_week().week())
+                } else {
+                    String::new()
+                },
+                if new_day {
+                    date.format("%Y-%m-%d").to_string()
+                } else {
+                    String::new()
+                },
+                if new_day {
+                    date.format("%a").to_string()
+                } else {
+                    String::new()
+                },
+                format!("@{}", part.id),
+                part.tags.join(", "),
+                part.start
+                    .with_timezone(&timezone)
+                    .format("%H:%M:%S")
+                    .to_string(),
+                match part.end {
+                    Some(end) => end.with_timezone(&timezone).format("%H:%M:%S").to_string(),
+                    None => "-".into(),
+                },
+                format_duration(duration),
+                if last_of_day {
+                    format_duration(day_total)
+                } else {
+                    String::new()
+                },
+            ],
+            None,
+        );
+        if last_of_day {
+            day_total = Duration::zero();
+        }
+        previous = Some(part);
+    }
+    let mut total_row = vec![String::new(); 8];
+    total_row.push(format_duration(total));
+    table.add_row(vec![], None);
+    table.add_row(
+        total_row,
+        Some(Color {
+            bold: true,
+            ..Color::default()
+        }),
+    );
+    Ok(table.render(verbose, color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SystemClock, Tag};
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    fn session(id: usize, start: (u32, u32), end: (u32, u32), tags: &[&str]) -> Session {
+        let timezone = FixedOffset::west(5 * 3600);
+        Session {
+            id,
+            start: timezone
+                .ymd(2021, 7, start.0)
+                .and_hms(start.1, 0, 0)
+                .with_timezone(&Utc),
+            end: Some(
+                timezone
+                    .ymd(2021, 7, end.0)
+                    .and_hms(end.1, 0, 0)
+                    .with_timezone(&Utc),
+            ),
+            tags: tags.iter().map(|tag| Tag::from(*tag)).collect(),
+            annotation: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn render_sessions_grouped_by_day() {
+        let data = TimewarriorData {
+            config: [("color", "off"), ("reports.summary.timezone", "-05:00")]
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            sessions: vec![
+                session(3, (11, 9), (11, 10), &["a"]),
+                session(2, (11, 22), (12, 1), &["b", "c"]),
+                session(1, (12, 8), (12, 9), &[]),
+            ],
+        };
+        let output = render(&data, &SystemClock).unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].starts_with("Wk"));
+        assert!(lines[2].starts_with("W27 2021-07-11 Sun @3 a    09:00:00 10:00:00 1:00:00"));
+        assert!(lines[3].ends_with("22:00:00 00:00:00 2:00:00 3:00:00"));
+        assert!(lines[4].starts_with("W28 2021-07-12 Mon @2 b, c 00:00:00 01:00:00 1:00:00"));
+        assert!(lines[5].ends_with("1:00:00 2:00:00"));
+        assert_eq!(lines[6], "");
+        assert_eq!(lines[7].trim(), "5:00:00");
+    }
+
+    #[test]
+    fn render_empty_report() {
+        let mut data = TimewarriorData {
+            config: Default::default(),
+            sessions: vec![],
+        };
+        assert_eq!(
+            render(&data, &SystemClock).unwrap(),
+            "No filtered data found.\n"
+        );
+        data.config.insert("verbose".into(), "off".into());
+        assert_eq!(render(&data, &SystemClock).unwrap(), "");
+    }
+}
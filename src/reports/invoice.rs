@@ -0,0 +1,248 @@
+//! An invoice over the tracked time, as plain text, Markdown or HTML
+
+use super::{format_duration, Alignment, Table};
+use crate::billing::{BillingRules, Invoice, LineItem};
+use crate::config::Color;
+use crate::{Clock, ReportError, TimewarriorData};
+use std::str::FromStr;
+
+/// The format an invoice is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceFormat {
+    /// An aligned plain text table
+    Text,
+    /// A Markdown table
+    Markdown,
+    /// An HTML document with a table
+    Html,
+}
+
+impl FromStr for InvoiceFormat {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" | "plain" => Ok(InvoiceFormat::Text),
+            "markdown" | "md" => Ok(InvoiceFormat::Markdown),
+            "html" => Ok(InvoiceFormat::Html),
+            _ => Err(ReportError::InvalidConfig {
+                key: "reports.invoice.format".into(),
+                value: value.into(),
+                expected: "\"text\", \"markdown\" or \"html\"".into(),
+            }),
+        }
+    }
+}
+
+const COLUMNS: [&str; 5] = ["Item", "Time", "Rate", "Discount", "Amount"];
+
+fn item_cells(item: &LineItem) -> Vec<String> {
+    vec![
+        item.tag.clone().unwrap_or_else(|| "Other".into()),
+        format_duration(item.duration),
+        format!("{}/h", item.rate),
+        if item.discount.is_zero() {
+            String::new()
+        } else {
+            item.discount.to_string()
+        },
+        item.amount.to_string(),
+    ]
+}
+
+/// The rows below the line items, with the label and the amount of each
+fn total_rows(invoice: &Invoice) -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+    for total in &invoice.totals {
+        if !invoice.discount.is_zero() {
+            rows.push(("Subtotal".into(), total.subtotal.to_string()));
+            rows.push((
+                format!("Discount {}", invoice.discount),
+                format!("-{}", total.discount),
+            ));
+        }
+        rows.push(("Total".into(), total.total.to_string()));
+    }
+    rows
+}
+
+/// Render the invoice as an aligned plain text table
+pub fn to_text(invoice: &Invoice, title: &str, color: bool) -> String {
+    let mut table = Table::new(&[
+        (COLUMNS[0], Alignment::Left),
+        (COLUMNS[1], Alignment::Right),
+        (COLUMNS[2], Alignment::Right),
+        (COLUMNS[3], Alignment::Right),
+        (COLUMNS[4], Alignment::Right),
+    ]);
+    for item in &invoice.items {
+        table.add_row(item_cells(item), None);
+    }
+    table.add_row(vec![], None);
+    for (label, amount) in total_rows(invoice) {
+        let color = if label == "Total" {
+            Some(Color {
+                bold: true,
+                ..Color::default()
+            })
+        } else {
+            None
+        };
+        table.add_row(
+            vec![label, String::new(), String::new(), String::new(), amount],
+            color,
+        );
+    }
+    format!("{}\n\n{}", title, table.render(true, color))
+}
+
+/// Escape a cell of a Markdown table
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Render the invoice as a Markdown table
+pub fn to_markdown(invoice: &Invoice, title: &str) -> String {
+    let mut output = format!("# {}\n\n", title);
+    output.push_str(&format!("| {} |\n", COLUMNS.join(" | ")));
+    output.push_str("|:-----|-----:|-----:|---------:|-------:|\n");
+    for item in &invoice.items {
+        let cells = item_cells(item)
+            .iter()
+            .map(|cell| escape_markdown(cell))
+            .collect::<Vec<String>>();
+        output.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    for (label, amount) in total_rows(invoice) {
+        output.push_str(&format!("| **{}** | | | | **{}** |\n", label, amount));
+    }
+    output
+}
+
+/// Escape text for HTML
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the invoice as an HTML document
+pub fn to_html(invoice: &Invoice, title: &str) -> String {
+    let title = escape_html(title);
+    let mut output = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n<table>\n",
+        title
+    );
+    let header = COLUMNS
+        .iter()
+        .map(|column| format!("<th>{}</th>", column))
+        .collect::<String>();
+    output.push_str(&format!(
+        "<thead>\n<tr>{}</tr>\n</thead>\n<tbody>\n",
+        header
+    ));
+    for item in &invoice.items {
+        let cells = item_cells(item)
+            .iter()
+            .map(|cell| format!("<td>{}</td>", escape_html(cell)))
+            .collect::<String>();
+        output.push_str(&format!("<tr>{}</tr>\n", cells));
+    }
+    output.push_str("</tbody>\n<tfoot>\n");
+    for (label, amount) in total_rows(invoice) {
+        output.push_str(&format!(
+            "<tr><th colspan=\"4\">{}</th><td>{}</td></tr>\n",
+            escape_html(&label),
+            escape_html(&amount)
+        ));
+    }
+    output.push_str("</tfoot>\n</table>\n</body>\n</html>\n");
+    output
+}
+
+/// Render an invoice over the tracked time within the report range
+///
+/// Rates, discounts and rounding are read as described in [`BillingRules::from_config`].
+/// `reports.invoice.format` selects `text`, `markdown` or `html`, `reports.invoice.title`
+/// sets the title. The time is rounded following `reports.rounding.scope`, per session by
+/// default. With the `day` scope, days are taken in the timezone from
+/// `reports.invoice.timezone` or `reports.timezone`.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let config = &data.config;
+    let rules = BillingRules::from_config(config)?;
+    let invoice = data.invoice(&rules, clock, &config.report_timezone("invoice")?)?;
+    let title = config
+        .report_setting("invoice", "title")
+        .unwrap_or("Invoice");
+    let format = match config.report_setting("invoice", "format") {
+        Some(format) => format.parse()?,
+        None => InvoiceFormat::Text,
+    };
+    Ok(match format {
+        InvoiceFormat::Text => to_text(&invoice, title, config.color()?),
+        InvoiceFormat::Markdown => to_markdown(&invoice, title),
+        InvoiceFormat::Html => to_html(&invoice, title),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SystemClock;
+
+    fn data(format: &str) -> TimewarriorData {
+        TimewarriorData::from_string(format!(
+            "color: off\n\
+             reports.invoice.format: {}\n\
+             reports.invoice.title: July <2021>\n\
+             reports.invoice.currency: EUR\n\
+             reports.invoice.rate.acme: 100\n\
+             reports.invoice.rate.a|b: 50\n\
+             reports.invoice.discount.acme: 10%\n\
+             reports.invoice.discount: 5%\n\
+             \n\
+             [\n\
+             {{\"id\":2,\"start\":\"20210711T080000Z\",\"end\":\"20210711T093000Z\",\"tags\":[\"acme.web\"]}},\n\
+             {{\"id\":1,\"start\":\"20210711T100000Z\",\"end\":\"20210711T110000Z\",\"tags\":[\"a|b\"]}}\n\
+             ]",
+            format
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn render_text_invoice() {
+        let output = render(&data("text"), &SystemClock).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<&str>>(),
+            vec![
+                "July <2021>",
+                "",
+                "Item            Time         Rate Discount     Amount",
+                "------------ ------- ------------ -------- ----------",
+                "acme         1:30:00 100.00 EUR/h     10 % 135.00 EUR",
+                "a|b          1:00:00  50.00 EUR/h           50.00 EUR",
+                "",
+                "Subtotal                                   185.00 EUR",
+                "Discount 5 %                                -9.25 EUR",
+                "Total                                      175.75 EUR",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_markdown_and_html_invoices() {
+        let output = render(&data("markdown"), &SystemClock).unwrap();
+        assert!(output.starts_with("# July <2021>\n\n| Item | Time |"));
+        assert!(output.contains("| a\\|b | 1:00:00 | 50.00 EUR/h |  | 50.00 EUR |\n"));
+        assert!(output.ends_with("| **Total** | | | | **175.75 EUR** |\n"));
+
+        let output = render(&data("html"), &SystemClock).unwrap();
+        assert!(output.contains("<h1>July &lt;2021&gt;</h1>"));
+        assert!(output.contains("<tr><td>acme</td><td>1:30:00</td><td>100.00 EUR/h</td><td>10 %</td><td>135.00 EUR</td></tr>"));
+        assert!(output.contains("<tr><th colspan=\"4\">Total</th><td>175.75 EUR</td></tr>"));
+
+        assert!(render(&data("pdf"), &SystemClock).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! Renderers for reports over the data passed by Timewarrior
+
+use crate::config::Color;
+use chrono::Duration;
+
+pub mod compliance;
+pub mod csv;
+pub mod day_summary;
+pub mod invoice;
+pub mod tag_summary;
+pub mod tag_tree;
+pub mod validate;
+pub mod week;
+pub mod worktime;
+
+/// Format a duration as `h:mm:ss`
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::Duration;
+/// use timewarrior_report::reports::format_duration;
+///
+/// assert_eq!(format_duration(Duration::seconds(27_245)), "7:34:05");
+/// assert_eq!(format_duration(Duration::minutes(-90)), "-1:30:00");
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let seconds = duration.num_seconds().abs();
+    format!(
+        "{}{}:{:02}:{:02}",
+        sign,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// The alignment of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Alignment {
+    Left,
+    Right,
+}
+
+/// A simple table with aligned columns, rendered as plain or colored text
+#[derive(Debug)]
+pub(crate) struct Table {
+    columns: Vec<(String, Alignment)>,
+    rows: Vec<(Vec<String>, Option<Color>)>,
+}
+
+impl Table {
+    pub(crate) fn new(columns: &[(&str, Alignment)]) -> Self {
+        Table {
+            columns: columns
+                .iter()
+                .map(|(title, alignment)| (title.to_string(), *alignment))
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row, which is painted in the given color if colors are enabled
+    pub(crate) fn add_row(&mut self, cells: Vec<String>, color: Option<Color>) {
+        self.rows.push((cells, color));
+    }
+
+    /// Render the table, with a header if `header` is set
+    ///
+    /// Without colors, the header is underlined with dashes.
+    pub(crate) fn render(&self, header: bool, color: bool) -> String {
+        let mut widths = self
+            .columns
+            .iter()
+            .map(|(title, _)| if header { title.chars().count() } else { 0 })
+            .collect::<Vec<usize>>();
+        for (cells, _) in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let pad = |cells: &[String]| {
+            self.columns
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(index, ((_, alignment), width))| {
+                    let cell = cells.get(index).map(String::as_str).unwrap_or("");
+                    match alignment {
+                        Alignment::Left => format!("{:<width$}", cell, width = width),
+                        Alignment::Right => format!("{:>width$}", cell, width = width),
+                    }
+                })
+                .collect::<Vec<String>>()
+        };
+        let format_row = |cells: &[String]| pad(cells).join(" ").trim_end().to_string();
+        let mut output = String::new();
+        if header {
+            let titles = self
+                .columns
+                .iter()
+                .map(|(title, _)| title.clone())
+                .collect::<Vec<String>>();
+            if color {
+                let underline = Color {
+                    underline: true,
+                    ..Color::default()
+                };
+                let cells = pad(&titles)
+                    .iter()
+                    .map(|title| underline.paint(title))
+                    .collect::<Vec<String>>();
+                output.push_str(&cells.join(" "));
+            } else {
+                output.push_str(&format_row(&titles));
+                output.push('\n');
+                let dashes = widths
+                    .iter()
+                    .map(|width| "-".repeat(*width))
+                    .collect::<Vec<String>>();
+                output.push_str(&dashes.join(" "));
+            }
+            output.push('\n');
+        }
+        for (cells, row_color) in &self.rows {
+            let line = format_row(cells);
+            match row_color {
+                Some(row_color) if color && !line.is_empty() => {
+                    output.push_str(&row_color.paint(&line))
+                }
+                _ => output.push_str(&line),
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
@@ -0,0 +1,308 @@
+//! A summary of the tracked time per tag or per combination of tags
+
+use super::{format_duration, Alignment, Table};
+use crate::config::{Color, ColorValue};
+use crate::{Clock, ReportError, TimewarriorData};
+use chrono::Duration;
+use std::str::FromStr;
+
+/// Width of the percentage bar in characters
+const BAR_WIDTH: usize = 20;
+
+/// The key a [`TagSummary`] is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Sort alphabetically by the tags
+    Name,
+    /// Sort by the tracked time
+    Duration,
+}
+
+impl FromStr for SortKey {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "name" | "tag" | "tags" => Ok(SortKey::Name),
+            "duration" | "time" => Ok(SortKey::Duration),
+            _ => Err(ReportError::InvalidConfig {
+                key: "reports.tag_summary.sort".into(),
+                value: value.into(),
+                expected: "\"name\" or \"duration\"".into(),
+            }),
+        }
+    }
+}
+
+/// A row of a [`TagSummary`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSummaryRow {
+    /// The tag or combination of tags, empty for sessions without tags
+    pub tags: Vec<String>,
+    /// The time tracked for the tags
+    pub duration: Duration,
+    /// The share of the total tracked time in percent
+    pub percentage: f64,
+}
+
+impl TagSummaryRow {
+    fn label(&self) -> String {
+        if self.tags.is_empty() {
+            "(no tags)".into()
+        } else {
+            self.tags.join(" ")
+        }
+    }
+}
+
+/// The tracked time per tag or per combination of tags within the report range
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSummary {
+    /// The rows of the summary
+    pub rows: Vec<TagSummaryRow>,
+    /// The total time tracked within the report range
+    pub total: Duration,
+}
+
+impl TagSummary {
+    /// Summarize the time per single tag
+    ///
+    /// A session with several tags counts towards each of them, so the percentages can add up
+    /// to more than 100 %. Time tracked without tags is listed in a row without tags.
+    pub fn by_tag(data: &TimewarriorData, clock: &dyn Clock) -> Result<Self, ReportError> {
+        let total = data.total_duration(clock)?;
+        let mut rows = data
+            .duration_by_tag(clock)?
+            .into_iter()
+            .map(|(tag, duration)| (vec![tag], duration))
+            .collect::<Vec<(Vec<String>, Duration)>>();
+        if let Some(untagged) = data.duration_by_tag_combination(clock)?.remove(&Vec::new()) {
+            rows.push((Vec::new(), untagged));
+        }
+        Ok(Self::from_durations(rows, total))
+    }
+
+    /// Summarize the time per combination of tags as they were attached to the sessions
+    pub fn by_combination(data: &TimewarriorData, clock: &dyn Clock) -> Result<Self, ReportError> {
+        let total = data.total_duration(clock)?;
+        let rows = data
+            .duration_by_tag_combination(clock)?
+            .into_iter()
+            .collect();
+        Ok(Self::from_durations(rows, total))
+    }
+
+    fn from_durations(rows: Vec<(Vec<String>, Duration)>, total: Duration) -> Self {
+        let rows = rows
+            .into_iter()
+            .map(|(tags, duration)| TagSummaryRow {
+                tags,
+                duration,
+                percentage: if total > Duration::zero() {
+                    duration.num_seconds() as f64 * 100.0 / total.num_seconds() as f64
+                } else {
+                    0.0
+                },
+            })
+            .collect();
+        TagSummary { rows, total }
+    }
+
+    /// Sort the rows by the given key
+    ///
+    /// Rows with equal durations are sorted by name.
+    pub fn sort(&mut self, key: SortKey, descending: bool) {
+        self.rows.sort_by(|a, b| {
+            let ordering = match key {
+                SortKey::Name => a.tags.cmp(&b.tags),
+                SortKey::Duration => a.duration.cmp(&b.duration).then(a.tags.cmp(&b.tags)),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    /// Render the summary as a table with a bar visualizing the percentage of each row
+    ///
+    /// With `color`, the bars are painted in the colors of the palette, cycling through it if
+    /// there are more rows than colors. Without it, plain `#` characters are used. The header is
+    /// only printed if `verbose` is set.
+    pub fn render(&self, verbose: bool, color: bool, palette: &[Color]) -> String {
+        let default_palette = (1..7)
+            .map(|index| Color {
+                foreground: Some(ColorValue::Basic(index)),
+                ..Color::default()
+            })
+            .collect::<Vec<Color>>();
+        let palette = if palette.is_empty() {
+            &default_palette
+        } else {
+            palette
+        };
+        let mut table = Table::new(&[
+            ("Tags", Alignment::Left),
+            ("Time", Alignment::Right),
+            ("Percent", Alignment::Right),
+            ("", Alignment::Left),
+        ]);
+        for (index, row) in self.rows.iter().enumerate() {
+            let length =
+                ((row.percentage / 100.0 * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+            let bar = if color {
+                palette[index % palette.len()].paint(&"\u{2588}".repeat(length))
+            } else {
+                "#".repeat(length)
+            };
+            table.add_row(
+                vec![
+                    row.label(),
+                    format_duration(row.duration),
+                    format!("{:.1} %", row.percentage),
+                    bar,
+                ],
+                None,
+            );
+        }
+        table.add_row(vec![], None);
+        table.add_row(
+            vec!["Total".into(), format_duration(self.total)],
+            Some(Color {
+                bold: true,
+                ..Color::default()
+            }),
+        );
+        table.render(verbose, color)
+    }
+}
+
+/// Render a summary of the tracked time per tag, configured through the report header
+///
+/// `reports.tag_summary.combinations` switches to a summary per combination of tags,
+/// `reports.tag_summary.sort` sorts by `name` or `duration` and
+/// `reports.tag_summary.descending` reverses the order. Colors follow the `color` setting and
+/// the theme palette, the `verbose` setting controls whether a header is printed.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let config = &data.config;
+    let verbose = config.verbose()?;
+    let mut summary = if config
+        .get_bool("reports.tag_summary.combinations")?
+        .unwrap_or(false)
+    {
+        TagSummary::by_combination(data, clock)?
+    } else {
+        TagSummary::by_tag(data, clock)?
+    };
+    let key = match config.report_setting("tag_summary", "sort") {
+        Some(value) => value.parse()?,
+        None => SortKey::Duration,
+    };
+    let descending = config
+        .get_bool("reports.tag_summary.descending")?
+        .unwrap_or(key == SortKey::Duration);
+    summary.sort(key, descending);
+    if summary.rows.is_empty() {
+        return Ok(if verbose {
+            "No filtered data found.\n".into()
+        } else {
+            String::new()
+        });
+    }
+    Ok(summary.render(verbose, config.color()?, &config.palette()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SystemClock;
+
+    fn data(config: &[(&str, &str)]) -> TimewarriorData {
+        let mut data = TimewarriorData::from_string(
+            "temp.report.start: 20210711T000000Z\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210711T080000Z\",\"end\":\"20210711T090000Z\",\"tags\":[\"b\"]},\n\
+             {\"id\":2,\"start\":\"20210711T090000Z\",\"end\":\"20210711T120000Z\",\"tags\":[\"b\",\"a\"]},\n\
+             {\"id\":1,\"start\":\"20210711T120000Z\",\"end\":\"20210711T160000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        for (key, value) in config {
+            data.config.insert(key.to_string(), value.to_string());
+        }
+        data
+    }
+
+    #[test]
+    fn summarize_by_tag() {
+        let mut summary = TagSummary::by_tag(&data(&[]), &SystemClock).unwrap();
+        summary.sort(SortKey::Duration, true);
+        assert_eq!(summary.total, Duration::hours(8));
+        let rows = summary
+            .rows
+            .iter()
+            .map(|row| (row.label(), row.duration.num_hours(), row.percentage))
+            .collect::<Vec<(String, i64, f64)>>();
+        assert_eq!(
+            rows,
+            vec![
+                ("b".into(), 4, 50.0),
+                ("(no tags)".into(), 4, 50.0),
+                ("a".into(), 3, 37.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_summary_by_combination() {
+        let output = render(
+            &data(&[
+                ("color", "off"),
+                ("reports.tag_summary.combinations", "on"),
+                ("reports.tag_summary.sort", "name"),
+            ]),
+            &SystemClock,
+        )
+        .unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "Tags         Time Percent");
+        assert_eq!(lines[2], "(no tags) 4:00:00  50.0 % ##########");
+        assert_eq!(lines[3], "a b       3:00:00  37.5 % ########");
+        assert_eq!(lines[4], "b         1:00:00  12.5 % ###");
+        assert_eq!(lines[6], "Total     8:00:00");
+    }
+
+    #[test]
+    fn render_without_header_unless_verbose() {
+        let output = render(&data(&[("color", "off"), ("verbose", "off")]), &SystemClock).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<&str>>(),
+            vec![
+                "b         4:00:00 50.0 % ##########",
+                "(no tags) 4:00:00 50.0 % ##########",
+                "a         3:00:00 37.5 % ########",
+                "",
+                "Total     8:00:00",
+            ]
+        );
+
+        let mut empty = data(&[("color", "off")]);
+        empty.sessions.clear();
+        assert_eq!(
+            render(&empty, &SystemClock).unwrap(),
+            "No filtered data found.\n"
+        );
+    }
+
+    #[test]
+    fn reject_unknown_sort_key() {
+        assert!(render(
+            &data(&[("reports.tag_summary.sort", "color")]),
+            &SystemClock
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! The tracked time rolled up along the tag hierarchy, e.g. `acme` → `acme.web`
+
+use super::{format_duration, Alignment, Table};
+use crate::config::Color;
+use crate::{Clock, ReportError, TagHierarchy, TagNode, TimewarriorData};
+
+/// Indentation of each level of the tree
+const INDENT: &str = "  ";
+
+fn add_rows(table: &mut Table, nodes: &[TagNode], depth: usize, hierarchy: &TagHierarchy) {
+    for node in nodes {
+        table.add_row(
+            vec![
+                format!("{}{}", INDENT.repeat(depth), node.tag.name(hierarchy)),
+                format_duration(node.duration),
+            ],
+            None,
+        );
+        add_rows(table, &node.children, depth + 1, hierarchy);
+    }
+}
+
+/// Render the tracked time per tag as an indented tree
+///
+/// Each level of a tag like `acme.web.login` is listed below the level above it, with the time
+/// of all sessions tagged with it or anything below it. The levels are separated by the
+/// characters in `reports.tag_separators`, `.` by default. The table ends with the total time
+/// tracked within the report range, including sessions without tags.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let hierarchy = data.config.tag_hierarchy();
+    let trees = data.duration_by_tag_tree(clock, &hierarchy)?;
+    let mut table = Table::new(&[("Tag", Alignment::Left), ("Time", Alignment::Right)]);
+    add_rows(&mut table, &trees, 0, &hierarchy);
+    table.add_row(vec![], None);
+    table.add_row(
+        vec!["Total".into(), format_duration(data.total_duration(clock)?)],
+        Some(Color {
+            bold: true,
+            ..Color::default()
+        }),
+    );
+    Ok(table.render(data.config.verbose()?, data.config.color()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SystemClock;
+
+    #[test]
+    fn render_indented_tree() {
+        let data = TimewarriorData::from_string(
+            "color: off\n\
+             reports.tag_separators: .:\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210711T080000Z\",\"end\":\"20210711T090000Z\",\"tags\":[\"acme:web.login\"]},\n\
+             {\"id\":2,\"start\":\"20210711T090000Z\",\"end\":\"20210711T120000Z\",\"tags\":[\"acme:api\",\"internal\"]},\n\
+             {\"id\":1,\"start\":\"20210711T120000Z\",\"end\":\"20210711T130000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let output = render(&data, &SystemClock).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<&str>>(),
+            vec![
+                "Tag          Time",
+                "--------- -------",
+                "acme      4:00:00",
+                "  api     3:00:00",
+                "  web     1:00:00",
+                "    login 1:00:00",
+                "internal  3:00:00",
+                "",
+                "Total     5:00:00",
+            ]
+        );
+    }
+}
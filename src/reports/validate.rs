@@ -0,0 +1,132 @@
+//! A list of overlapping sessions and untracked gaps, to find mistakes in the tracked data
+
+use super::{format_duration, Alignment, Table};
+use crate::{Clock, DateRange, ReportError, ReportTimeZone, TimewarriorData};
+use chrono::{DateTime, Duration, Utc};
+
+/// Format of the start and end of a finding
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn format_date(date: DateTime<Utc>, timezone: &ReportTimeZone) -> String {
+    date.with_timezone(timezone).format(DATE_FORMAT).to_string()
+}
+
+/// Render all overlaps and gaps within the report range as a table
+///
+/// Gaps are searched within the report range, but not beyond the current time of the clock.
+/// Without a report range, the range from the first start to the last end of the sessions is
+/// used. Gaps shorter than `reports.validate.min_gap` are left out. Times are shown in the
+/// timezone from `reports.validate.timezone` or `reports.timezone`.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let config = &data.config;
+    let timezone = config.report_timezone("validate")?;
+    let min_gap = config
+        .get_duration("reports.validate.min_gap")?
+        .unwrap_or_else(Duration::zero);
+    let now = clock.now();
+    let first_start = data.sessions.iter().map(|session| session.start).min();
+    let last_end = data
+        .sessions
+        .iter()
+        .map(|session| session.end.unwrap_or(now))
+        .max();
+    let within = match (
+        config.report_start()?.or(first_start),
+        config.report_end()?.or(last_end),
+    ) {
+        (Some(start), Some(end)) => Some(DateRange::new(start, end.min(now))),
+        _ => None,
+    };
+
+    let mut table = Table::new(&[
+        ("Finding", Alignment::Left),
+        ("IDs", Alignment::Left),
+        ("Start", Alignment::Left),
+        ("End", Alignment::Left),
+        ("Time", Alignment::Right),
+    ]);
+    let mut rows = Vec::new();
+    for overlap in data.overlaps(clock) {
+        rows.push((
+            overlap.range,
+            vec![
+                "Overlap".to_string(),
+                format!("@{}, @{}", overlap.first, overlap.second),
+            ],
+        ));
+    }
+    for gap in within
+        .map(|within| data.gaps(within, clock))
+        .unwrap_or_default()
+    {
+        if gap.range.duration() >= min_gap {
+            let ids = [gap.before, gap.after]
+                .iter()
+                .flatten()
+                .map(|id| format!("@{}", id))
+                .collect::<Vec<String>>();
+            rows.push((gap.range, vec!["Gap".to_string(), ids.join(" - ")]));
+        }
+    }
+    if rows.is_empty() {
+        return Ok(if config.verbose()? {
+            "No overlaps or gaps found.\n".into()
+        } else {
+            String::new()
+        });
+    }
+    rows.sort_by_key(|(range, _)| (range.start, range.end));
+    for (range, mut cells) in rows {
+        cells.push(format_date(range.start, &timezone));
+        cells.push(format_date(range.end, &timezone));
+        cells.push(format_duration(range.duration()));
+        table.add_row(cells, None);
+    }
+    Ok(table.render(config.verbose()?, config.color()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedClock;
+    use chrono::TimeZone;
+
+    fn data(config: &str) -> TimewarriorData {
+        TimewarriorData::from_string(format!(
+            "color: off\n\
+             reports.timezone: UTC\n\
+             {}\n\
+             \n\
+             [\n\
+             {{\"id\":3,\"start\":\"20210711T080000Z\",\"end\":\"20210711T100000Z\",\"tags\":[]}},\n\
+             {{\"id\":2,\"start\":\"20210711T093000Z\",\"end\":\"20210711T110000Z\",\"tags\":[]}},\n\
+             {{\"id\":1,\"start\":\"20210711T110500Z\",\"end\":\"20210711T120000Z\",\"tags\":[]}}\n\
+             ]",
+            config
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn list_overlaps_and_gaps() {
+        let clock = FixedClock(Utc.ymd(2021, 7, 11).and_hms(12, 30, 0));
+        let output = render(&data("temp.report.start: 20210711T070000Z"), &clock).unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[2],
+            "Gap     @3      2021-07-11 07:00:00 2021-07-11 08:00:00 1:00:00"
+        );
+        assert_eq!(
+            lines[3],
+            "Overlap @3, @2  2021-07-11 09:30:00 2021-07-11 10:00:00 0:30:00"
+        );
+        assert_eq!(
+            lines[4],
+            "Gap     @2 - @1 2021-07-11 11:00:00 2021-07-11 11:05:00 0:05:00"
+        );
+
+        let output = render(&data("reports.validate.min_gap: 10min"), &clock).unwrap();
+        assert_eq!(output.lines().count(), 3);
+    }
+}
@@ -0,0 +1,405 @@
+//! A calendar grid of a week with days as columns and hours as rows, similar to `timew week`
+
+use crate::clip::{local_date, start_of_day};
+use crate::config::{invalid, Color, ColorValue};
+use crate::{Clock, ReportConfig, ReportError, Session, TimewarriorData};
+use chrono::{Datelike, Duration, NaiveDate, TimeZone};
+use std::collections::BTreeSet;
+
+/// Width of the column with the hours
+const HOUR_WIDTH: usize = 5;
+
+/// Options for the layout of the week grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekOptions {
+    /// The width of the grid in characters, like the width of the terminal
+    pub width: usize,
+    /// The first hour shown, fitted to the sessions of the week if `None`
+    pub first_hour: Option<u32>,
+    /// The hour the grid ends with, up to 24, fitted to the sessions of the week if `None`
+    pub last_hour: Option<u32>,
+    /// The number of rows per hour, which must divide 60
+    pub rows_per_hour: u32,
+}
+
+impl Default for WeekOptions {
+    fn default() -> Self {
+        WeekOptions {
+            width: 80,
+            first_hour: None,
+            last_hour: None,
+            rows_per_hour: 1,
+        }
+    }
+}
+
+impl WeekOptions {
+    /// Read the options from the `reports.week.*` keys of the configuration
+    ///
+    /// `reports.week.width` sets the width of the grid, `reports.week.first_hour` from 0 to 23
+    /// and `reports.week.last_hour` up to 24 the hours shown, where the last hour has to come
+    /// after the first, and `reports.week.rows_per_hour` the rows per hour, like `4` for
+    /// quarter hours.
+    pub fn from_config(config: &ReportConfig) -> Result<Self, ReportError> {
+        let mut options = WeekOptions::default();
+        if let Some(width) = config.get_integer("reports.week.width")? {
+            options.width = width.max(0) as usize;
+        }
+        let hour = |setting: &str, first: i64, last: i64| -> Result<Option<u32>, ReportError> {
+            let key = format!("reports.week.{}", setting);
+            match config.get_integer(&key)? {
+                Some(hour) if (first..=last).contains(&hour) => Ok(Some(hour as u32)),
+                Some(hour) => Err(invalid(
+                    &key,
+                    &hour.to_string(),
+                    &format!("an hour from {} to {}", first, last),
+                )),
+                None => Ok(None),
+            }
+        };
+        options.first_hour = hour("first_hour", 0, 23)?;
+        options.last_hour = hour("last_hour", 1, 24)?;
+        if let (Some(first), Some(last)) = (options.first_hour, options.last_hour) {
+            if last <= first {
+                return Err(invalid(
+                    "reports.week.last_hour",
+                    &last.to_string(),
+                    &format!("an hour after reports.week.first_hour ({})", first),
+                ));
+            }
+        }
+        if let Some(rows) = config.get_integer("reports.week.rows_per_hour")? {
+            if rows < 1 || 60 % rows != 0 {
+                return Err(invalid(
+                    "reports.week.rows_per_hour",
+                    &rows.to_string(),
+                    "a divisor of 60",
+                ));
+            }
+            options.rows_per_hour = rows as u32;
+        }
+        Ok(options)
+    }
+}
+
+/// A part of a session on one day, in seconds since the start of the day
+struct Block<'a> {
+    session: &'a Session,
+    day: usize,
+    start: i64,
+    end: i64,
+}
+
+/// The colors blocks are painted in if the theme has no palette
+fn default_palette() -> Vec<Color> {
+    (1..7)
+        .map(|index| Color {
+            foreground: Some(ColorValue::Basic(0)),
+            background: Some(ColorValue::Basic(index)),
+            ..Color::default()
+        })
+        .collect()
+}
+
+/// The label of a block, its tags or its ID if it has none
+fn label(session: &Session) -> String {
+    if session.tags.is_empty() {
+        format!("@{}", session.id)
+    } else {
+        session.tags.join(", ")
+    }
+}
+
+/// Pad or cut a text to exactly the width
+fn fit(text: &str, width: usize) -> String {
+    let text = text.chars().take(width).collect::<String>();
+    format!("{:<width$}", text, width = width)
+}
+
+/// Render the week starting on the given day as a grid of the given width
+///
+/// Each day is a column and each row covers an hour, or a part of it. Sessions are clipped to
+/// the report range and drawn as blocks starting with `|`, with their tags in the first row.
+/// A row shows the session covering most of its time. With `color`, blocks are painted in the
+/// colors of the theme palette, one per combination of tags. Sessions which have not ended
+/// yet are drawn up to the current time of the clock.
+pub fn render_week<Tz: TimeZone>(
+    data: &TimewarriorData,
+    first_day: NaiveDate,
+    options: &WeekOptions,
+    color: bool,
+    clock: &dyn Clock,
+    timezone: &Tz,
+) -> Result<String, ReportError> {
+    let clipped = data.clipped_to_range(clock)?;
+    let mut blocks = Vec::new();
+    for session in &clipped.sessions {
+        for part in session.split_at_midnight(clock, timezone) {
+            let date = local_date(part.start, timezone);
+            let day = (date - first_day).num_days();
+            if !(0..7).contains(&day) {
+                continue;
+            }
+            let midnight = start_of_day(date, timezone);
+            let end = part.end.unwrap_or_else(|| clock.now());
+            blocks.push(Block {
+                session,
+                day: day as usize,
+                start: (part.start - midnight).num_seconds(),
+                end: (end - midnight).num_seconds(),
+            });
+        }
+    }
+
+    let first_hour = options.first_hour.unwrap_or_else(|| {
+        let first = blocks.iter().map(|block| block.start / 3600).min();
+        first.unwrap_or(8).clamp(0, 23) as u32
+    });
+    let first_hour = first_hour.min(23);
+    let last_hour = options.last_hour.unwrap_or_else(|| {
+        let last = blocks.iter().map(|block| (block.end + 3599) / 3600).max();
+        last.unwrap_or(18).clamp(1, 24) as u32
+    });
+    let last_hour = last_hour.clamp(first_hour + 1, 24);
+
+    let mut palette = Vec::new();
+    if color {
+        palette = data.config.palette()?;
+        if palette.is_empty() {
+            palette = default_palette();
+        }
+    }
+    let labels = blocks
+        .iter()
+        .map(|block| label(block.session))
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect::<Vec<String>>();
+    let paint = |label: &str, text: String| match labels.iter().position(|known| known == label) {
+        Some(index) if !palette.is_empty() => palette[index % palette.len()].paint(&text),
+        _ => text,
+    };
+
+    let column_width = (options.width.saturating_sub(HOUR_WIDTH) / 7).max(2) - 1;
+    let mut output = String::new();
+    let mut header = fit(&format!("W{}", first_day.iso_week().week()), HOUR_WIDTH);
+    for day in 0..7 {
+        let date = first_day + Duration::days(day);
+        header.push(' ');
+        header.push_str(&fit(&date.format("%a %d").to_string(), column_width));
+    }
+    output.push_str(header.trim_end());
+    output.push('\n');
+    if !color {
+        output.push_str(&"-".repeat(HOUR_WIDTH));
+        for _ in 0..7 {
+            output.push(' ');
+            output.push_str(&"-".repeat(column_width));
+        }
+        output.push('\n');
+    }
+
+    let row_seconds = i64::from(3600 / options.rows_per_hour);
+    let mut previous: [Option<usize>; 7] = [None; 7];
+    for row in first_hour * options.rows_per_hour..last_hour * options.rows_per_hour {
+        let row_start = i64::from(row) * row_seconds;
+        let row_end = row_start + row_seconds;
+        let mut line = if row % options.rows_per_hour == 0 {
+            fit(
+                &format!("{:02}:00", row / options.rows_per_hour),
+                HOUR_WIDTH,
+            )
+        } else {
+            fit("", HOUR_WIDTH)
+        };
+        for (day, previous) in previous.iter_mut().enumerate() {
+            let block = blocks
+                .iter()
+                .filter(|block| block.day == day)
+                .map(|block| (block, block.end.min(row_end) - block.start.max(row_start)))
+                .filter(|(_, overlap)| *overlap > 0)
+                .max_by_key(|(block, overlap)| (*overlap, -block.start))
+                .map(|(block, _)| block);
+            line.push(' ');
+            match block {
+                Some(block) => {
+                    let label = label(block.session);
+                    let text = if *previous == Some(block.session.id) {
+                        fit("|", column_width)
+                    } else {
+                        fit(&format!("|{}", label), column_width)
+                    };
+                    line.push_str(&paint(&label, text));
+                    *previous = Some(block.session.id);
+                }
+                None => {
+                    line.push_str(&fit("", column_width));
+                    *previous = None;
+                }
+            }
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Render a week grid for every week within the report range
+///
+/// The weeks start on Monday and are taken in the timezone from `reports.week.timezone` or
+/// `reports.timezone`. Without a report range, the weeks with sessions are rendered, or the
+/// current week if there are none. The layout is read as described in
+/// [`WeekOptions::from_config`].
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let config = &data.config;
+    let options = WeekOptions::from_config(config)?;
+    let timezone = config.report_timezone("week")?;
+    let monday =
+        |date: NaiveDate| date - Duration::days(date.weekday().num_days_from_monday().into());
+    let now = clock.now();
+    let mut weeks = BTreeSet::new();
+    match config.report_start()? {
+        Some(start) => {
+            let end = config.report_end()?.unwrap_or(now).min(now).max(start);
+            let mut week = monday(local_date(start, &timezone));
+            let last = monday(local_date(end, &timezone));
+            while week <= last {
+                weeks.insert(week);
+                week += Duration::weeks(1);
+            }
+        }
+        None => {
+            for session in &data.sessions {
+                for part in session.split_at_midnight(clock, &timezone) {
+                    weeks.insert(monday(local_date(part.start, &timezone)));
+                }
+            }
+        }
+    }
+    if weeks.is_empty() {
+        weeks.insert(monday(local_date(now, &timezone)));
+    }
+    let color = config.color()?;
+    let mut grids = Vec::new();
+    for week in weeks {
+        grids.push(render_week(data, week, &options, color, clock, &timezone)?);
+    }
+    Ok(grids.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FixedClock, ReportTimeZone};
+    use chrono::Utc;
+
+    fn data() -> TimewarriorData {
+        TimewarriorData::from_string(
+            "color: off\n\
+             reports.timezone: utc\n\
+             reports.week.width: 60\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210712T080000Z\",\"end\":\"20210712T103000Z\",\"tags\":[\"acme\"]},\n\
+             {\"id\":2,\"start\":\"20210713T091000Z\",\"end\":\"20210713T095000Z\",\"tags\":[\"b\"]},\n\
+             {\"id\":1,\"start\":\"20210714T100000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn render_week_grid() {
+        let clock = FixedClock(Utc.ymd(2021, 7, 14).and_hms(11, 30, 0));
+        let output = render(&data(), &clock).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<&str>>(),
+            vec![
+                "W28   Mon 12 Tue 13 Wed 14 Thu 15 Fri 16 Sat 17 Sun 18",
+                "----- ------ ------ ------ ------ ------ ------ ------",
+                "08:00 |acme",
+                "09:00 |      |b",
+                "10:00 |             |@1",
+                "11:00               |",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_colored_blocks() {
+        let options = WeekOptions {
+            width: 40,
+            first_hour: Some(8),
+            last_hour: Some(10),
+            rows_per_hour: 2,
+        };
+        let clock = FixedClock(Utc.ymd(2021, 7, 14).and_hms(11, 30, 0));
+        let output = render_week(
+            &data(),
+            NaiveDate::from_ymd(2021, 7, 12),
+            &options,
+            true,
+            &clock,
+            &ReportTimeZone::Utc,
+        )
+        .unwrap();
+        let acme = |text| format!("\x1b[30;42m{}\x1b[0m", text);
+        let b = |text| format!("\x1b[30;43m{}\x1b[0m", text);
+        assert_eq!(
+            output.lines().map(String::from).collect::<Vec<String>>(),
+            vec![
+                "W28   Mon  Tue  Wed  Thu  Fri  Sat  Sun".to_string(),
+                format!("08:00 {}", acme("|acm")),
+                format!("      {}", acme("|   ")),
+                format!("09:00 {} {}", acme("|   "), b("|b  ")),
+                format!("      {} {}", acme("|   "), b("|   ")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_hours() {
+        for config in &[
+            [
+                ("reports.week.first_hour", "24"),
+                ("reports.week.last_hour", "24"),
+            ],
+            [
+                ("reports.week.first_hour", "10"),
+                ("reports.week.last_hour", "10"),
+            ],
+            [
+                ("reports.week.first_hour", "12"),
+                ("reports.week.last_hour", "9"),
+            ],
+            [
+                ("reports.week.first_hour", "8"),
+                ("reports.week.last_hour", "0"),
+            ],
+        ] {
+            let config = config
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<ReportConfig>();
+            assert!(WeekOptions::from_config(&config).is_err(), "{:?}", config);
+        }
+
+        let options = WeekOptions {
+            first_hour: Some(24),
+            last_hour: Some(24),
+            ..WeekOptions::default()
+        };
+        let clock = FixedClock(Utc.ymd(2021, 7, 14).and_hms(11, 30, 0));
+        let output = render_week(
+            &data(),
+            NaiveDate::from_ymd(2021, 7, 12),
+            &options,
+            false,
+            &clock,
+            &ReportTimeZone::Utc,
+        )
+        .unwrap();
+        assert_eq!(output.lines().last(), Some("23:00"));
+    }
+}
@@ -0,0 +1,123 @@
+//! The flexitime account, with the over- and undertime per day and the running balance
+
+use super::{format_duration, Alignment, Table};
+use crate::{Clock, ReportError, TimewarriorData, WorkTime};
+use chrono::Duration;
+
+/// Format a balance with an explicit sign, like `+1:30:00`
+fn format_balance(duration: Duration) -> String {
+    if duration > Duration::zero() {
+        format!("+{}", format_duration(duration))
+    } else {
+        format_duration(duration)
+    }
+}
+
+/// Render the balance of tracked and target time for every day within the report range
+///
+/// Targets and the carry-over are read as described in [`WorkTime::from_config`]. Days are
+/// taken in the timezone from `reports.worktime.timezone` or `reports.timezone`. The table
+/// starts with the carry-over and ends with the balance after the last day, followed by the
+/// utilization, the share of the working time of the schedule which was tracked.
+pub fn render(data: &TimewarriorData, clock: &dyn Clock) -> Result<String, ReportError> {
+    let config = &data.config;
+    let worktime = WorkTime::from_config(config)?;
+    let timezone = config.report_timezone("worktime")?;
+    let days = data.worktime_balance(&worktime, clock, &timezone)?;
+    if days.is_empty() {
+        return Ok(if config.verbose()? {
+            "No filtered data found.\n".into()
+        } else {
+            String::new()
+        });
+    }
+
+    let mut table = Table::new(&[
+        ("Date", Alignment::Left),
+        ("Day", Alignment::Left),
+        ("Target", Alignment::Right),
+        ("Tracked", Alignment::Right),
+        ("Difference", Alignment::Right),
+        ("Balance", Alignment::Right),
+    ]);
+    let mut carry_over = vec![String::from("Carry-over")];
+    carry_over.resize(5, String::new());
+    carry_over.push(format_balance(worktime.carry_over));
+    table.add_row(carry_over, None);
+    for day in &days {
+        table.add_row(
+            vec![
+                day.date.format("%Y-%m-%d").to_string(),
+                day.date.format("%a").to_string(),
+                format_duration(day.target),
+                format_duration(day.tracked),
+                format_balance(day.difference),
+                format_balance(day.balance),
+            ],
+            None,
+        );
+    }
+    let expected = days
+        .iter()
+        .fold(Duration::zero(), |total, day| total + day.expected);
+    if expected > Duration::zero() {
+        let tracked = days
+            .iter()
+            .fold(Duration::zero(), |total, day| total + day.tracked);
+        let utilization = 100.0 * tracked.num_seconds() as f64 / expected.num_seconds() as f64;
+        let mut row = vec![String::from("Utilization")];
+        row.resize(5, String::new());
+        row.push(format!("{:.1} %", utilization));
+        table.add_row(vec![], None);
+        table.add_row(row, None);
+    }
+    Ok(table.render(config.verbose()?, config.color()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedClock;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn render_balance_table() {
+        let data = TimewarriorData::from_string(
+            "color: off\n\
+             reports.timezone: utc\n\
+             reports.worktime.friday: 8h\n\
+             reports.worktime.monday: 8h\n\
+             reports.worktime.carry_over: -1h\n\
+             exclusions.monday: <8:00 >18:00\n\
+             exclusions.friday: <8:00 >18:00\n\
+             exclusions.saturday: >0:00\n\
+             exclusions.sunday: >0:00\n\
+             temp.report.start: 20210709T000000Z\n\
+             \n\
+             [\n\
+             {\"id\":2,\"start\":\"20210709T080000Z\",\"end\":\"20210709T173000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210712T080000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let clock = FixedClock(Utc.ymd(2021, 7, 12).and_hms(14, 0, 0));
+        assert_eq!(
+            render(&data, &clock)
+                .unwrap()
+                .lines()
+                .collect::<Vec<&str>>(),
+            vec![
+                "Date        Day  Target Tracked Difference  Balance",
+                "----------- --- ------- ------- ---------- --------",
+                "Carry-over                                 -1:00:00",
+                "2021-07-09  Fri 8:00:00 9:30:00   +1:30:00 +0:30:00",
+                "2021-07-10  Sat 0:00:00 0:00:00    0:00:00 +0:30:00",
+                "2021-07-11  Sun 0:00:00 0:00:00    0:00:00 +0:30:00",
+                "2021-07-12  Mon 8:00:00 6:00:00   -2:00:00 -1:30:00",
+                "",
+                "Utilization                                  96.9 %",
+            ]
+        );
+    }
+}
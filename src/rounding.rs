@@ -0,0 +1,336 @@
+//! Rounding of tracked time to billing increments, like 6 or 15 minutes
+
+use crate::aggregate::add;
+use crate::clip::local_date;
+use crate::{Clock, ReportConfig, ReportError, Session, TimewarriorData};
+use chrono::{Duration, NaiveDate, TimeZone};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// The direction durations are rounded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round up to the next increment
+    Up,
+    /// Round down to the previous increment
+    Down,
+    /// Round to the nearest increment, halfway up
+    Nearest,
+}
+
+impl FromStr for RoundingMode {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "up" | "ceil" => Ok(RoundingMode::Up),
+            "down" | "floor" => Ok(RoundingMode::Down),
+            "nearest" => Ok(RoundingMode::Nearest),
+            _ => Err(ReportError::InvalidConfig {
+                key: "reports.rounding.mode".into(),
+                value: value.into(),
+                expected: "\"up\", \"down\" or \"nearest\"".into(),
+            }),
+        }
+    }
+}
+
+/// The amount of time which is rounded at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingScope {
+    /// Every session is rounded on its own
+    Session,
+    /// The time of each group is rounded per day
+    Day,
+    /// The total time of each group is rounded
+    Total,
+}
+
+impl FromStr for RoundingScope {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "session" | "sessions" => Ok(RoundingScope::Session),
+            "day" | "days" | "daily" => Ok(RoundingScope::Day),
+            "total" | "tag" | "tags" => Ok(RoundingScope::Total),
+            _ => Err(ReportError::InvalidConfig {
+                key: "reports.rounding.scope".into(),
+                value: value.into(),
+                expected: "\"session\", \"day\" or \"total\"".into(),
+            }),
+        }
+    }
+}
+
+/// Rules for rounding tracked time, e.g. as agreed in a contract
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::Duration;
+/// use timewarrior_report::{Rounding, RoundingMode};
+///
+/// let rounding = Rounding {
+///     increment: Duration::minutes(15),
+///     minimum: Duration::minutes(30),
+///     ..Rounding::default()
+/// };
+/// assert_eq!(rounding.round(Duration::minutes(5)), Duration::minutes(30));
+/// assert_eq!(rounding.round(Duration::minutes(46)), Duration::minutes(60));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rounding {
+    /// The increment durations are rounded to, no rounding happens if it is zero
+    pub increment: Duration,
+    /// The direction durations are rounded in
+    pub mode: RoundingMode,
+    /// The amount of time which is rounded at once
+    pub scope: RoundingScope,
+    /// The least time billed for any tracked time, applied after rounding
+    pub minimum: Duration,
+}
+
+impl Default for Rounding {
+    fn default() -> Self {
+        Rounding {
+            increment: Duration::zero(),
+            mode: RoundingMode::Up,
+            scope: RoundingScope::Session,
+            minimum: Duration::zero(),
+        }
+    }
+}
+
+impl Rounding {
+    /// Read the rounding rules from the `reports.rounding.*` keys of the configuration
+    ///
+    /// `reports.rounding.increment` sets the increment, like `6min` or `0:15`,
+    /// `reports.rounding.mode` rounds `up`, `down` or to the `nearest` increment,
+    /// `reports.rounding.scope` rounds each `session`, the time per `day` or only the `total`
+    /// and `reports.rounding.minimum` sets the least billable time. The defaults round every
+    /// session up, without a minimum. Without an increment and a minimum, time is not rounded.
+    pub fn from_config(config: &ReportConfig) -> Result<Self, ReportError> {
+        let mut rounding = Rounding::default();
+        if let Some(increment) = config.get_duration("reports.rounding.increment")? {
+            rounding.increment = increment;
+        }
+        if let Some(mode) = config.report_setting("rounding", "mode") {
+            rounding.mode = mode.parse()?;
+        }
+        if let Some(scope) = config.report_setting("rounding", "scope") {
+            rounding.scope = scope.parse()?;
+        }
+        if let Some(minimum) = config.get_duration("reports.rounding.minimum")? {
+            rounding.minimum = minimum;
+        }
+        Ok(rounding)
+    }
+
+    /// Round a duration to the increment and raise it to the minimum
+    ///
+    /// A duration of zero stays zero, as nothing was tracked.
+    pub fn round(&self, duration: Duration) -> Duration {
+        if duration <= Duration::zero() {
+            return duration;
+        }
+        let increment = self.increment.num_seconds();
+        let mut rounded = duration;
+        if increment > 0 {
+            let seconds = duration.num_seconds();
+            let increments = match self.mode {
+                RoundingMode::Up => (seconds + increment - 1) / increment,
+                RoundingMode::Down => seconds / increment,
+                RoundingMode::Nearest => (seconds + increment / 2) / increment,
+            };
+            rounded = Duration::seconds(increments * increment);
+        }
+        rounded.max(self.minimum)
+    }
+
+    /// Round the durations of groups of sessions according to the scope
+    ///
+    /// `spans` are the durations of all sessions with the day they fall on and the groups they
+    /// count towards. Depending on the scope, each span, the time of each group per day or the
+    /// total time of each group is rounded.
+    fn round_groups<K: Ord + Clone>(
+        &self,
+        spans: Vec<(NaiveDate, Vec<K>, Duration)>,
+    ) -> BTreeMap<K, Duration> {
+        let mut days = BTreeMap::new();
+        let mut totals = BTreeMap::new();
+        for (date, keys, duration) in spans {
+            for key in keys {
+                match self.scope {
+                    RoundingScope::Session => add(&mut totals, key, self.round(duration)),
+                    RoundingScope::Day => add(&mut days, (key, date), duration),
+                    RoundingScope::Total => add(&mut totals, key, duration),
+                }
+            }
+        }
+        for ((key, _), duration) in days {
+            add(&mut totals, key, self.round(duration));
+        }
+        if self.scope == RoundingScope::Total {
+            for duration in totals.values_mut() {
+                *duration = self.round(*duration);
+            }
+        }
+        totals
+    }
+}
+
+impl TimewarriorData {
+    /// Rounded duration of all sessions within the report range per tag
+    ///
+    /// With the day scope, sessions are split at midnight in the timezone and the time per tag
+    /// and day is rounded. Sessions without tags are not included.
+    pub fn rounded_duration_by_tag<Tz: TimeZone>(
+        &self,
+        rounding: &Rounding,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<BTreeMap<String, Duration>, ReportError> {
+        self.rounded_durations(rounding, clock, timezone, |session| {
+            Ok(session.tags.iter().map(|tag| tag.to_string()).collect())
+        })
+    }
+
+    /// Rounded duration of all sessions within the report range
+    ///
+    /// With the total scope, the time of each combination of tags is rounded on its own, so
+    /// the result is the sum of the lines of an invoice listing each combination.
+    pub fn rounded_total_duration<Tz: TimeZone>(
+        &self,
+        rounding: &Rounding,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<Duration, ReportError> {
+        let durations = self.rounded_durations(rounding, clock, timezone, |session| {
+            let mut tags = session.tags.clone();
+            tags.sort();
+            tags.dedup();
+            Ok(vec![tags])
+        })?;
+        Ok(durations
+            .values()
+            .fold(Duration::zero(), |total, duration| total + *duration))
+    }
+
+    /// Rounded duration of all sessions within the report range per group
+    ///
+    /// Each session counts towards all groups returned for it, an error returned for a session
+    /// is passed on. Sessions are only split at midnight for the day scope, so every other scope
+    /// rounds sessions spanning midnight as a whole.
+    pub(crate) fn rounded_durations<Tz: TimeZone, K: Ord + Clone>(
+        &self,
+        rounding: &Rounding,
+        clock: &dyn Clock,
+        timezone: &Tz,
+        groups: impl Fn(&Session) -> Result<Vec<K>, ReportError>,
+    ) -> Result<BTreeMap<K, Duration>, ReportError> {
+        let mut spans = Vec::new();
+        for session in &self.clipped_to_range(clock)?.sessions {
+            let groups = groups(session)?;
+            if rounding.scope == RoundingScope::Day {
+                for part in session.split_at_midnight(clock, timezone) {
+                    let date = local_date(part.start, timezone);
+                    spans.push((date, groups.clone(), part.duration(clock)));
+                }
+            } else {
+                let date = local_date(session.start, timezone);
+                spans.push((date, groups, session.duration(clock)));
+            }
+        }
+        Ok(rounding.round_groups(spans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReportTimeZone, SystemClock};
+
+    fn rounding(scope: RoundingScope) -> Rounding {
+        Rounding {
+            increment: Duration::minutes(15),
+            scope,
+            ..Rounding::default()
+        }
+    }
+
+    fn data() -> TimewarriorData {
+        TimewarriorData::from_string(
+            "[\n\
+             {\"id\":4,\"start\":\"20210711T080000Z\",\"end\":\"20210711T080500Z\",\"tags\":[\"a\"]},\n\
+             {\"id\":3,\"start\":\"20210711T090000Z\",\"end\":\"20210711T090500Z\",\"tags\":[\"a\"]},\n\
+             {\"id\":2,\"start\":\"20210712T090000Z\",\"end\":\"20210712T090500Z\",\"tags\":[\"a\",\"b\"]},\n\
+             {\"id\":1,\"start\":\"20210712T100000Z\",\"end\":\"20210712T100100Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_durations() {
+        let minutes = |minutes| Duration::minutes(minutes);
+        let mut rounding = rounding(RoundingScope::Session);
+        assert_eq!(rounding.round(minutes(1)), minutes(15));
+        assert_eq!(rounding.round(minutes(15)), minutes(15));
+        assert_eq!(rounding.round(Duration::zero()), Duration::zero());
+        rounding.mode = RoundingMode::Down;
+        assert_eq!(rounding.round(minutes(29)), minutes(15));
+        rounding.mode = RoundingMode::Nearest;
+        rounding.increment = minutes(6);
+        assert_eq!(rounding.round(minutes(8)), minutes(6));
+        assert_eq!(rounding.round(minutes(9)), minutes(12));
+        rounding.minimum = minutes(30);
+        assert_eq!(rounding.round(minutes(9)), minutes(30));
+    }
+
+    #[test]
+    fn round_per_session_day_and_total() {
+        let data = data();
+        let by_tag = |scope| {
+            data.rounded_duration_by_tag(&rounding(scope), &SystemClock, &ReportTimeZone::Utc)
+                .unwrap()
+        };
+        let total = |scope| {
+            data.rounded_total_duration(&rounding(scope), &SystemClock, &ReportTimeZone::Utc)
+                .unwrap()
+        };
+        assert_eq!(by_tag(RoundingScope::Session)["a"], Duration::minutes(45));
+        assert_eq!(by_tag(RoundingScope::Day)["a"], Duration::minutes(30));
+        assert_eq!(by_tag(RoundingScope::Total)["a"], Duration::minutes(15));
+        assert_eq!(by_tag(RoundingScope::Total)["b"], Duration::minutes(15));
+        assert_eq!(total(RoundingScope::Session), Duration::minutes(60));
+        assert_eq!(total(RoundingScope::Day), Duration::minutes(45));
+        assert_eq!(total(RoundingScope::Total), Duration::minutes(45));
+    }
+
+    #[test]
+    fn read_rounding_from_config() {
+        let config = [
+            ("reports.rounding.increment", "6min"),
+            ("reports.rounding.mode", "nearest"),
+            ("reports.rounding.scope", "day"),
+            ("reports.rounding.minimum", "0:30"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<ReportConfig>();
+        assert_eq!(
+            Rounding::from_config(&config).unwrap(),
+            Rounding {
+                increment: Duration::minutes(6),
+                mode: RoundingMode::Nearest,
+                scope: RoundingScope::Day,
+                minimum: Duration::minutes(30),
+            }
+        );
+        let mut config = ReportConfig::default();
+        config.insert("reports.rounding.mode".into(), "sideways".into());
+        assert!(Rounding::from_config(&config).is_err());
+    }
+}
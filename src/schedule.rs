@@ -0,0 +1,331 @@
+//! The working schedule defined by the `exclusions.*` and `holidays.*` settings of Timewarrior
+
+use crate::clip::{local_date, local_instant};
+use crate::config::invalid;
+use crate::{DateRange, ReportConfig, ReportError};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+/// The names of the weekdays as used in the configuration, starting on Monday
+pub(crate) const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// Seconds in a day, the end of a period lasting until midnight
+const DAY: u32 = 24 * 3600;
+
+/// The working time of each day, with excluded times, days off and holidays
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::{Duration, NaiveDate};
+/// use timewarrior_report::{ReportConfig, Schedule};
+///
+/// let mut config = ReportConfig::default();
+/// config.insert("exclusions.monday".into(), "<8:00 12:00-12:30 >16:30".into());
+/// config.insert("holidays.en-US.2021_07_05".into(), "Independence Day".into());
+/// let schedule = Schedule::from_config(&config).unwrap();
+/// assert_eq!(schedule.working_time(NaiveDate::from_ymd(2021, 7, 12)), Duration::hours(8));
+/// assert_eq!(schedule.working_time(NaiveDate::from_ymd(2021, 7, 5)), Duration::zero());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    /// Excluded times per weekday as seconds since midnight, starting on Monday
+    exclusions: [Vec<(u32, u32)>; 7],
+    /// Days which are worked (`true`) or taken off (`false`) regardless of their weekday
+    days: BTreeMap<NaiveDate, bool>,
+    /// Holidays with their names
+    holidays: BTreeMap<NaiveDate, String>,
+}
+
+impl Schedule {
+    /// Read the schedule from the exclusions and holidays in the configuration
+    ///
+    /// `exclusions.monday` to `exclusions.sunday` list the excluded times of each weekday, like
+    /// `<8:00 12:00-12:45 >17:30`, where `>0:00` excludes the whole day.
+    /// `exclusions.days.<date>` makes a day a working day with `on` or a day off with `off`.
+    /// `holidays.<locale>.<date>` names a holiday, which is not worked. Dates are written like
+    /// `2021_12_24`. Other `exclusions.*` keys are ignored.
+    pub fn from_config(config: &ReportConfig) -> Result<Self, ReportError> {
+        let mut schedule = Schedule::default();
+        for (key, value) in config.raw() {
+            let mut parts = key.splitn(3, '.');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("exclusions"), Some("days"), Some(date)) => {
+                    let worked = match value.trim().to_lowercase().as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(invalid(key, value, "\"on\" or \"off\"")),
+                    };
+                    schedule.days.insert(parse_date(key, date)?, worked);
+                }
+                (Some("exclusions"), Some(weekday), None) => {
+                    if let Some(index) = WEEKDAYS.iter().position(|name| *name == weekday) {
+                        schedule.exclusions[index] = parse_exclusions(key, value)?;
+                    }
+                }
+                (Some("holidays"), Some(_), Some(date)) => {
+                    schedule
+                        .holidays
+                        .insert(parse_date(key, date)?, value.trim().to_string());
+                }
+                _ => {}
+            }
+        }
+        Ok(schedule)
+    }
+
+    /// The name of the holiday on the given day, if it is one
+    pub fn holiday(&self, date: NaiveDate) -> Option<&str> {
+        self.holidays.get(&date).map(String::as_str)
+    }
+
+    /// Whether any time of the given day is worked
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        !self.working_periods(date).is_empty()
+    }
+
+    /// The working time of the given day, as it is scheduled in local time
+    pub fn working_time(&self, date: NaiveDate) -> Duration {
+        length(&self.working_periods(date))
+    }
+
+    /// The working time of a regular day of the weekday, counted from Monday as 0
+    pub(crate) fn weekday_working_time(&self, weekday: usize) -> Duration {
+        length(&complement(&self.exclusions[weekday]))
+    }
+
+    /// Whether the instant lies outside of the working time of its day in the timezone
+    pub fn is_excluded<Tz: TimeZone>(&self, instant: DateTime<Utc>, timezone: &Tz) -> bool {
+        !self
+            .working_ranges(local_date(instant, timezone), timezone)
+            .iter()
+            .any(|range| range.contains(instant))
+    }
+
+    /// The working time within the range, with days taken in the timezone
+    ///
+    /// Unlike [`Schedule::working_time`], the time is measured in real time, so it is shorter
+    /// or longer on days with a change to daylight saving time within working hours.
+    pub fn expected_time<Tz: TimeZone>(&self, range: DateRange, timezone: &Tz) -> Duration {
+        let mut expected = Duration::zero();
+        if range.end <= range.start {
+            return expected;
+        }
+        let mut date = local_date(range.start, timezone);
+        let last = local_date(range.end - Duration::nanoseconds(1), timezone);
+        while date <= last {
+            for working in self.working_ranges(date, timezone) {
+                let start = working.start.max(range.start);
+                let end = working.end.min(range.end);
+                if end > start {
+                    expected = expected + (end - start);
+                }
+            }
+            date = date.succ();
+        }
+        expected
+    }
+
+    /// The working periods of the given day as seconds since midnight
+    ///
+    /// A day made a working day by `exclusions.days` is worked like its weekday, or like the
+    /// first weekday with working time if its weekday is excluded completely.
+    fn working_periods(&self, date: NaiveDate) -> Vec<(u32, u32)> {
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        match self.days.get(&date) {
+            Some(false) => Vec::new(),
+            Some(true) => {
+                let periods = complement(&self.exclusions[weekday]);
+                if periods.is_empty() {
+                    self.exclusions
+                        .iter()
+                        .map(|exclusions| complement(exclusions))
+                        .find(|periods| !periods.is_empty())
+                        .unwrap_or_default()
+                } else {
+                    periods
+                }
+            }
+            None if self.holidays.contains_key(&date) => Vec::new(),
+            None => complement(&self.exclusions[weekday]),
+        }
+    }
+
+    /// The working periods of the given day as instants in the timezone
+    fn working_ranges<Tz: TimeZone>(&self, date: NaiveDate, timezone: &Tz) -> Vec<DateRange> {
+        let instant = |seconds: u32| {
+            if seconds >= DAY {
+                local_instant(date.succ().and_hms(0, 0, 0), timezone)
+            } else {
+                let time = NaiveTime::from_num_seconds_from_midnight(seconds, 0);
+                local_instant(date.and_time(time), timezone)
+            }
+        };
+        self.working_periods(date)
+            .into_iter()
+            .map(|(start, end)| DateRange::new(instant(start), instant(end)))
+            .collect()
+    }
+}
+
+/// Parse the date of an `exclusions.days` or `holidays` key, like `2021_12_24`
+fn parse_date(key: &str, date: &str) -> Result<NaiveDate, ReportError> {
+    NaiveDate::parse_from_str(date, "%Y_%m_%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .map_err(|_| invalid(key, date, "a date like 2021_12_24 in the key"))
+}
+
+/// Parse a time of day like `8:00` or `17:30:15` as seconds since midnight, up to `24:00`
+fn parse_time(value: &str) -> Option<u32> {
+    let mut parts = value.split(':');
+    let hours: u32 = parts.next()?.parse().ok()?;
+    let minutes: u32 = parts.next()?.parse().ok()?;
+    let seconds: u32 = match parts.next() {
+        Some(seconds) => seconds.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() || hours > 24 || minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+    let time = hours * 3600 + minutes * 60 + seconds;
+    if time > DAY {
+        return None;
+    }
+    Some(time)
+}
+
+/// Parse the excluded times of a weekday, like `<8:00 12:00-12:45 >17:30`
+fn parse_exclusions(key: &str, value: &str) -> Result<Vec<(u32, u32)>, ReportError> {
+    value
+        .split_whitespace()
+        .map(|exclusion| {
+            let range = if let Some(end) = exclusion.strip_prefix('<') {
+                parse_time(end).map(|end| (0, end))
+            } else if let Some(start) = exclusion.strip_prefix('>') {
+                parse_time(start).map(|start| (start, DAY))
+            } else {
+                exclusion.split_once('-').and_then(|(start, end)| {
+                    Some((parse_time(start)?, parse_time(end)?)).filter(|(start, end)| start <= end)
+                })
+            };
+            range.ok_or_else(|| invalid(key, value, "exclusions like <8:00 12:00-12:45 >17:30"))
+        })
+        .collect()
+}
+
+/// The periods of a day which are not excluded
+fn complement(exclusions: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut exclusions = exclusions.to_vec();
+    exclusions.sort_unstable();
+    let mut periods = Vec::new();
+    let mut start = 0;
+    for (excluded_start, excluded_end) in exclusions {
+        if excluded_start > start {
+            periods.push((start, excluded_start));
+        }
+        start = start.max(excluded_end);
+    }
+    if start < DAY {
+        periods.push((start, DAY));
+    }
+    periods
+}
+
+/// The total length of periods given as seconds since midnight
+fn length(periods: &[(u32, u32)]) -> Duration {
+    periods
+        .iter()
+        .map(|(start, end)| Duration::seconds(i64::from(end - start)))
+        .fold(Duration::zero(), |total, duration| total + duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn schedule() -> Schedule {
+        let config = [
+            ("exclusions.monday", "<8:00 12:00-12:30 >16:30"),
+            ("exclusions.tuesday", "<8:00 12:00-12:30 >16:30"),
+            ("exclusions.saturday", ">0:00"),
+            ("exclusions.sunday", ">0:00"),
+            ("exclusions.days.2021_07_10", "on"),
+            ("exclusions.days.2021_07_13", "off"),
+            ("holidays.en-US.2021_07_05", "Independence Day"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<ReportConfig>();
+        Schedule::from_config(&config).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2021, 7, day)
+    }
+
+    #[test]
+    fn working_time_per_day() {
+        let schedule = schedule();
+        assert_eq!(schedule.working_time(date(12)), Duration::hours(8));
+        assert_eq!(schedule.working_time(date(14)), Duration::hours(24));
+        assert_eq!(schedule.working_time(date(11)), Duration::zero());
+        assert_eq!(schedule.holiday(date(5)), Some("Independence Day"));
+        assert!(!schedule.is_working_day(date(5)));
+        assert!(!schedule.is_working_day(date(13)));
+        assert_eq!(schedule.working_time(date(10)), Duration::hours(8));
+    }
+
+    #[test]
+    fn excluded_instants_and_expected_time() {
+        let schedule = schedule();
+        let timezone = FixedOffset::east(2 * 3600);
+        let at = |day, hour, minute| {
+            timezone
+                .ymd(2021, 7, day)
+                .and_hms(hour, minute, 0)
+                .with_timezone(&Utc)
+        };
+        assert!(schedule.is_excluded(at(12, 7, 59), &timezone));
+        assert!(!schedule.is_excluded(at(12, 8, 0), &timezone));
+        assert!(schedule.is_excluded(at(12, 12, 15), &timezone));
+        assert!(schedule.is_excluded(at(13, 10, 0), &timezone));
+        assert_eq!(
+            schedule.expected_time(DateRange::new(at(9, 0, 0), at(13, 10, 0)), &timezone),
+            Duration::hours(24 + 8 + 8)
+        );
+        assert_eq!(
+            schedule.expected_time(DateRange::new(at(12, 10, 0), at(12, 13, 0)), &timezone),
+            Duration::minutes(150)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_exclusions() {
+        for (key, value) in &[
+            ("exclusions.monday", "<8"),
+            ("exclusions.monday", "13:00-12:00"),
+            ("exclusions.monday", "<99999999:00"),
+            ("exclusions.monday", ">24:00:01"),
+            ("exclusions.days.2021_07_10", "maybe"),
+            ("holidays.en-US.someday", "Holiday"),
+        ] {
+            let mut config = ReportConfig::default();
+            config.insert(key.to_string(), value.to_string());
+            assert!(
+                Schedule::from_config(&config).is_err(),
+                "{} = {}",
+                key,
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Tags of sessions and their hierarchy
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+
+/// A tag attached to a session
+///
+/// Tags can form a hierarchy, like `client.project.task` or `client:project`, where each
+/// separator opens a new level below the tag in front of it. Which characters separate the
+/// levels is given by a [`TagHierarchy`].
+///
+/// # Example
+///
+/// ```rust
+/// use timewarrior_report::{Tag, TagHierarchy};
+///
+/// let hierarchy = TagHierarchy::new(".:");
+/// let tag = Tag::from("acme.web:login");
+/// assert!(tag.is_under("acme", &hierarchy));
+/// assert_eq!(tag.name(&hierarchy), "login");
+/// assert_eq!(tag.parent(&hierarchy), Some(Tag::from("acme.web")));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Tag(String);
+
+impl Tag {
+    /// The tag as written in Timewarrior
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The tag itself and all tags above it, starting with the topmost level
+    ///
+    /// For `acme.web.login` these are `acme`, `acme.web` and `acme.web.login`.
+    pub fn path(&self, hierarchy: &TagHierarchy) -> Vec<Tag> {
+        let mut path = self
+            .0
+            .char_indices()
+            .filter(|(index, character)| *index > 0 && hierarchy.is_separator(*character))
+            .map(|(index, _)| Tag::from(&self.0[..index]))
+            .filter(|tag| {
+                !tag.0
+                    .ends_with(|character| hierarchy.is_separator(character))
+            })
+            .collect::<Vec<Tag>>();
+        path.push(self.clone());
+        path
+    }
+
+    /// The tag one level above, `None` for a tag on the topmost level
+    pub fn parent(&self, hierarchy: &TagHierarchy) -> Option<Tag> {
+        let mut path = self.path(hierarchy);
+        path.pop();
+        path.pop()
+    }
+
+    /// The last level of the tag, e.g. `login` for `acme.web.login`
+    pub fn name(&self, hierarchy: &TagHierarchy) -> &str {
+        match self.parent(hierarchy) {
+            Some(parent) => self.0[parent.0.len()..]
+                .trim_start_matches(|character| hierarchy.is_separator(character)),
+            None => &self.0,
+        }
+    }
+
+    /// The number of levels above the tag, 0 for a tag on the topmost level
+    pub fn depth(&self, hierarchy: &TagHierarchy) -> usize {
+        self.path(hierarchy).len() - 1
+    }
+
+    /// Whether the tag is the given tag or lies somewhere below it
+    pub fn is_under(&self, ancestor: &str, hierarchy: &TagHierarchy) -> bool {
+        self.0 == ancestor
+            || (self.0.starts_with(ancestor)
+                && self.0[ancestor.len()..]
+                    .starts_with(|character| hierarchy.is_separator(character)))
+    }
+}
+
+impl Deref for Tag {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Tag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Tag {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for Tag {
+    fn from(tag: String) -> Self {
+        Tag(tag)
+    }
+}
+
+impl From<&str> for Tag {
+    fn from(tag: &str) -> Self {
+        Tag(tag.into())
+    }
+}
+
+impl From<Tag> for String {
+    fn from(tag: Tag) -> Self {
+        tag.0
+    }
+}
+
+impl PartialEq<str> for Tag {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Tag {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Tag {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+/// The characters separating the levels of hierarchical tags
+///
+/// By default only `.` separates levels. The configuration can set others through
+/// `reports.tag_separators`, see [`ReportConfig::tag_hierarchy`](crate::ReportConfig::tag_hierarchy).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagHierarchy {
+    separators: Vec<char>,
+}
+
+impl TagHierarchy {
+    /// A hierarchy where each of the given characters separates two levels
+    pub fn new(separators: &str) -> Self {
+        TagHierarchy {
+            separators: separators.chars().collect(),
+        }
+    }
+
+    /// Whether the character separates two levels
+    pub fn is_separator(&self, character: char) -> bool {
+        self.separators.contains(&character)
+    }
+}
+
+impl Default for TagHierarchy {
+    fn default() -> Self {
+        TagHierarchy::new(".")
+    }
+}
+
+/// A tag with the time tracked for it and everything below it
+///
+/// Created by [`TimewarriorData::duration_by_tag_tree`](crate::TimewarriorData::duration_by_tag_tree).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagNode {
+    /// The tag of this level
+    pub tag: Tag,
+    /// The time tracked for sessions with this tag or any tag below it
+    pub duration: Duration,
+    /// The tags one level below, sorted by name
+    pub children: Vec<TagNode>,
+}
+
+impl TagNode {
+    /// Build the trees from durations which contain every level of every tag
+    pub(crate) fn build(
+        durations: &[(Tag, Duration)],
+        parent: Option<&Tag>,
+        hierarchy: &TagHierarchy,
+    ) -> Vec<TagNode> {
+        durations
+            .iter()
+            .filter(|(tag, _)| tag.parent(hierarchy).as_ref() == parent)
+            .map(|(tag, duration)| TagNode {
+                tag: tag.clone(),
+                duration: *duration,
+                children: TagNode::build(durations, Some(tag), hierarchy),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_tag_hierarchy() {
+        let hierarchy = TagHierarchy::new(".:");
+        let tag = Tag::from("acme:web.login");
+        assert_eq!(
+            tag.path(&hierarchy),
+            vec!["acme", "acme:web", "acme:web.login"]
+        );
+        assert_eq!(tag.depth(&hierarchy), 2);
+        assert_eq!(tag.name(&hierarchy), "login");
+        assert!(tag.is_under("acme:web", &hierarchy));
+        assert!(tag.is_under("acme:web.login", &hierarchy));
+        assert!(!tag.is_under("acme:we", &hierarchy));
+        assert!(!Tag::from("acme:web").is_under("acme", &TagHierarchy::default()));
+        assert_eq!(Tag::from(".hidden").parent(&hierarchy), None);
+        assert_eq!(Tag::from("a..b").path(&hierarchy), vec!["a", "a..b"]);
+    }
+}
@@ -0,0 +1,204 @@
+//! Timezones reports can be rendered in
+
+use crate::ReportError;
+use chrono::{FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone};
+use std::fmt;
+use std::str::FromStr;
+
+/// The timezone a report is rendered in
+///
+/// Sessions are stored in UTC and only converted into a timezone to determine days, weeks and
+/// displayed times. A timezone is written as `local`, `UTC`, a fixed offset like `+02:00`, or,
+/// with the `chrono-tz` feature, as an IANA name like `Europe/Berlin`.
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::{TimeZone, Utc};
+/// use timewarrior_report::ReportTimeZone;
+///
+/// let timezone: ReportTimeZone = "+05:30".parse().unwrap();
+/// let instant = Utc.ymd(2021, 7, 11).and_hms(20, 0, 0).with_timezone(&timezone);
+/// assert_eq!(instant.format("%Y-%m-%d %H:%M").to_string(), "2021-07-12 01:30");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportTimeZone {
+    /// The timezone of the machine the report runs on
+    #[default]
+    Local,
+    /// Coordinated Universal Time
+    Utc,
+    /// A fixed offset from UTC
+    Fixed(FixedOffset),
+    /// A timezone from the IANA database
+    #[cfg(feature = "chrono-tz")]
+    Named(chrono_tz::Tz),
+}
+
+impl TimeZone for ReportTimeZone {
+    type Offset = FixedOffset;
+
+    fn from_offset(offset: &FixedOffset) -> Self {
+        ReportTimeZone::Fixed(*offset)
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+        match self {
+            ReportTimeZone::Local => fix(Local.offset_from_local_date(local)),
+            ReportTimeZone::Utc => LocalResult::Single(FixedOffset::east(0)),
+            ReportTimeZone::Fixed(offset) => LocalResult::Single(*offset),
+            #[cfg(feature = "chrono-tz")]
+            ReportTimeZone::Named(timezone) => fix(timezone.offset_from_local_date(local)),
+        }
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+        match self {
+            ReportTimeZone::Local => fix(Local.offset_from_local_datetime(local)),
+            ReportTimeZone::Utc => LocalResult::Single(FixedOffset::east(0)),
+            ReportTimeZone::Fixed(offset) => LocalResult::Single(*offset),
+            #[cfg(feature = "chrono-tz")]
+            ReportTimeZone::Named(timezone) => fix(timezone.offset_from_local_datetime(local)),
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+        match self {
+            ReportTimeZone::Local => Local.offset_from_utc_date(utc).fix(),
+            ReportTimeZone::Utc => FixedOffset::east(0),
+            ReportTimeZone::Fixed(offset) => *offset,
+            #[cfg(feature = "chrono-tz")]
+            ReportTimeZone::Named(timezone) => timezone.offset_from_utc_date(utc).fix(),
+        }
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+        match self {
+            ReportTimeZone::Local => Local.offset_from_utc_datetime(utc).fix(),
+            ReportTimeZone::Utc => FixedOffset::east(0),
+            ReportTimeZone::Fixed(offset) => *offset,
+            #[cfg(feature = "chrono-tz")]
+            ReportTimeZone::Named(timezone) => timezone.offset_from_utc_datetime(utc).fix(),
+        }
+    }
+}
+
+fn fix<O: Offset>(result: LocalResult<O>) -> LocalResult<FixedOffset> {
+    match result {
+        LocalResult::None => LocalResult::None,
+        LocalResult::Single(offset) => LocalResult::Single(offset.fix()),
+        LocalResult::Ambiguous(earliest, latest) => {
+            LocalResult::Ambiguous(earliest.fix(), latest.fix())
+        }
+    }
+}
+
+impl FromStr for ReportTimeZone {
+    type Err = ReportError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "local" | "Local" => return Ok(ReportTimeZone::Local),
+            "utc" | "UTC" | "Z" => return Ok(ReportTimeZone::Utc),
+            _ => (),
+        }
+        if let Some(offset) = parse_offset(value) {
+            return Ok(ReportTimeZone::Fixed(offset));
+        }
+        #[cfg(feature = "chrono-tz")]
+        {
+            if let Ok(timezone) = value.parse::<chrono_tz::Tz>() {
+                return Ok(ReportTimeZone::Named(timezone));
+            }
+        }
+        Err(ReportError::Other(format!(
+            "Unknown timezone \"{}\"",
+            value
+        )))
+    }
+}
+
+impl fmt::Display for ReportTimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReportTimeZone::Local => write!(f, "local"),
+            ReportTimeZone::Utc => write!(f, "UTC"),
+            ReportTimeZone::Fixed(offset) => write!(f, "{}", offset),
+            #[cfg(feature = "chrono-tz")]
+            ReportTimeZone::Named(timezone) => write!(f, "{}", timezone.name()),
+        }
+    }
+}
+
+/// Parse an offset like `+02:00`, `-0530` or `+01`
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match value.chars().next()? {
+        '+' => (1, &value[1..]),
+        '-' => (-1, &value[1..]),
+        _ => return None,
+    };
+    let digits = rest.replace(':', "");
+    if !digits.chars().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes): (i32, i32) = match digits.len() {
+        2 => (digits.parse().ok()?, 0),
+        4 => (digits[..2].parse().ok()?, digits[2..].parse().ok()?),
+        _ => return None,
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn parse_timezones() {
+        assert_eq!(
+            "local".parse::<ReportTimeZone>().unwrap(),
+            ReportTimeZone::Local
+        );
+        assert_eq!(
+            "UTC".parse::<ReportTimeZone>().unwrap(),
+            ReportTimeZone::Utc
+        );
+        assert_eq!(
+            "-03:30".parse::<ReportTimeZone>().unwrap(),
+            ReportTimeZone::Fixed(FixedOffset::west(3 * 3600 + 1800))
+        );
+        assert!("+25:00".parse::<ReportTimeZone>().is_err());
+        assert!("Mars/Olympus_Mons".parse::<ReportTimeZone>().is_err());
+    }
+
+    #[test]
+    fn convert_into_timezone() {
+        let instant = Utc.ymd(2021, 7, 11).and_hms(22, 30, 0);
+        let converted = instant.with_timezone(&ReportTimeZone::Fixed(FixedOffset::east(7200)));
+        assert_eq!(
+            converted.naive_local(),
+            NaiveDate::from_ymd(2021, 7, 12).and_hms(0, 30, 0)
+        );
+        assert_eq!(converted, instant);
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    fn convert_into_named_timezone() {
+        let timezone = "Europe/Berlin".parse::<ReportTimeZone>().unwrap();
+        let winter = Utc
+            .ymd(2021, 1, 11)
+            .and_hms(12, 0, 0)
+            .with_timezone(&timezone);
+        let summer = Utc
+            .ymd(2021, 7, 11)
+            .and_hms(12, 0, 0)
+            .with_timezone(&timezone);
+        assert_eq!(winter.format("%H:%M").to_string(), "13:00");
+        assert_eq!(summer.format("%H:%M").to_string(), "14:00");
+    }
+}
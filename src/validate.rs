@@ -0,0 +1,178 @@
+//! Detection of overlapping sessions and of untracked gaps between sessions
+
+use crate::{Clock, DateRange, Session, TimewarriorData};
+use chrono::Duration;
+
+/// Two sessions which were tracked at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap {
+    /// ID of the session starting first
+    pub first: usize,
+    /// ID of the session starting within the first one
+    pub second: usize,
+    /// The time both sessions cover
+    pub range: DateRange,
+}
+
+/// A stretch of time in which no session was tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    /// ID of the session ending at the start of the gap, `None` at the start of the range
+    pub before: Option<usize>,
+    /// ID of the session starting at the end of the gap, `None` at the end of the range
+    pub after: Option<usize>,
+    /// The untracked time
+    pub range: DateRange,
+}
+
+impl TimewarriorData {
+    /// All pairs of sessions which overlap, sorted by the start of the overlap
+    ///
+    /// Sessions which have not ended yet are treated as running until the current time of the
+    /// clock.
+    pub fn overlaps(&self, clock: &dyn Clock) -> Vec<Overlap> {
+        let spans = self.sorted_spans(clock);
+        let mut overlaps = Vec::new();
+        for (index, (first, first_span)) in spans.iter().enumerate() {
+            for (second, second_span) in &spans[index + 1..] {
+                if second_span.start >= first_span.end {
+                    break;
+                }
+                let range = DateRange::new(second_span.start, first_span.end.min(second_span.end));
+                if range.duration() > Duration::zero() {
+                    overlaps.push(Overlap {
+                        first: first.id,
+                        second: second.id,
+                        range,
+                    });
+                }
+            }
+        }
+        overlaps.sort_by_key(|overlap| (overlap.range.start, overlap.range.end));
+        overlaps
+    }
+
+    /// All stretches of time within the range in which no session was tracked
+    ///
+    /// Sessions which have not ended yet are treated as running until the current time of the
+    /// clock.
+    pub fn gaps(&self, within: DateRange, clock: &dyn Clock) -> Vec<Gap> {
+        let mut gaps = Vec::new();
+        let mut tracked_until = within.start;
+        let mut before = None;
+        for (session, span) in self.sorted_spans(clock) {
+            if span.end <= within.start || span.start >= within.end {
+                continue;
+            }
+            if span.start > tracked_until {
+                gaps.push(Gap {
+                    before,
+                    after: Some(session.id),
+                    range: DateRange::new(tracked_until, span.start),
+                });
+            }
+            if span.end > tracked_until {
+                tracked_until = span.end;
+                before = Some(session.id);
+            }
+        }
+        if tracked_until < within.end {
+            gaps.push(Gap {
+                before,
+                after: None,
+                range: DateRange::new(tracked_until, within.end),
+            });
+        }
+        gaps
+    }
+
+    /// The sessions with their start and end, sorted by start
+    fn sorted_spans(&self, clock: &dyn Clock) -> Vec<(&Session, DateRange)> {
+        let now = clock.now();
+        let mut spans = self
+            .sessions
+            .iter()
+            .map(|session| {
+                let end = session.end.unwrap_or(now).max(session.start);
+                (session, DateRange::new(session.start, end))
+            })
+            .collect::<Vec<(&Session, DateRange)>>();
+        spans.sort_by_key(|(session, span)| (span.start, span.end, session.id));
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedClock;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn data() -> TimewarriorData {
+        TimewarriorData::from_string(
+            "[\n\
+             {\"id\":4,\"start\":\"20210711T080000Z\",\"end\":\"20210711T100000Z\",\"tags\":[]},\n\
+             {\"id\":3,\"start\":\"20210711T090000Z\",\"end\":\"20210711T093000Z\",\"tags\":[]},\n\
+             {\"id\":2,\"start\":\"20210711T094500Z\",\"end\":\"20210711T110000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210711T120000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap()
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2021, 7, 11).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn find_overlapping_sessions() {
+        let overlaps = data().overlaps(&FixedClock(at(13, 0)));
+        assert_eq!(
+            overlaps,
+            vec![
+                Overlap {
+                    first: 4,
+                    second: 3,
+                    range: DateRange::new(at(9, 0), at(9, 30)),
+                },
+                Overlap {
+                    first: 4,
+                    second: 2,
+                    range: DateRange::new(at(9, 45), at(10, 0)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn find_gaps_within_range() {
+        let gaps = data().gaps(DateRange::new(at(7, 0), at(14, 0)), &FixedClock(at(13, 0)));
+        assert_eq!(
+            gaps,
+            vec![
+                Gap {
+                    before: None,
+                    after: Some(4),
+                    range: DateRange::new(at(7, 0), at(8, 0)),
+                },
+                Gap {
+                    before: Some(2),
+                    after: Some(1),
+                    range: DateRange::new(at(11, 0), at(12, 0)),
+                },
+                Gap {
+                    before: Some(1),
+                    after: None,
+                    range: DateRange::new(at(13, 0), at(14, 0)),
+                },
+            ]
+        );
+        assert!(data()
+            .gaps(
+                DateRange::new(at(8, 30), at(10, 30)),
+                &FixedClock(at(13, 0))
+            )
+            .is_empty());
+    }
+}
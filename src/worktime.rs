@@ -0,0 +1,245 @@
+//! Overtime and flexitime balance against target hours per weekday
+
+use crate::clip::{local_date, start_of_day};
+use crate::config::{invalid, parse_duration};
+use crate::schedule::WEEKDAYS;
+use crate::{Clock, DateRange, ReportConfig, ReportError, Schedule, TimewarriorData};
+use chrono::{Datelike, Duration, NaiveDate, TimeZone};
+
+/// Target working hours per weekday, with the balance carried over from before
+///
+/// Days which are not worked according to the schedule, like holidays, have no target.
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::{Duration, NaiveDate};
+/// use timewarrior_report::WorkTime;
+///
+/// let mut worktime = WorkTime::default();
+/// worktime.targets[0] = Duration::hours(8);
+/// assert_eq!(worktime.target(NaiveDate::from_ymd(2021, 7, 12)), Duration::hours(8));
+/// assert_eq!(worktime.target(NaiveDate::from_ymd(2021, 7, 13)), Duration::zero());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkTime {
+    /// The target time per weekday, starting on Monday
+    pub targets: [Duration; 7],
+    /// The balance before the first day, negative for undertime
+    pub carry_over: Duration,
+    /// The schedule deciding which days are worked
+    pub schedule: Schedule,
+}
+
+impl Default for WorkTime {
+    fn default() -> Self {
+        WorkTime {
+            targets: [Duration::zero(); 7],
+            carry_over: Duration::zero(),
+            schedule: Schedule::default(),
+        }
+    }
+}
+
+impl WorkTime {
+    /// Read the target hours from the `reports.worktime.*` keys of the configuration
+    ///
+    /// `reports.worktime.monday` to `reports.worktime.sunday` set the target time per weekday,
+    /// like `8h` or `7:30`. Weekdays without a target take the working time left by
+    /// `exclusions.<weekday>` if that is set and have none otherwise. Holidays and days off are
+    /// taken from the [`Schedule`]. `reports.worktime.carry_over` sets the starting balance,
+    /// which may be negative like `-2h`.
+    pub fn from_config(config: &ReportConfig) -> Result<Self, ReportError> {
+        let mut worktime = WorkTime {
+            schedule: Schedule::from_config(config)?,
+            ..WorkTime::default()
+        };
+        for (index, weekday) in WEEKDAYS.iter().enumerate() {
+            if let Some(duration) = config.get_duration(&format!("reports.worktime.{}", weekday))? {
+                worktime.targets[index] = duration;
+            } else if config.get(&format!("exclusions.{}", weekday)).is_some() {
+                worktime.targets[index] = worktime.schedule.weekday_working_time(index);
+            }
+        }
+        if let Some(value) = config.report_setting("worktime", "carry_over") {
+            worktime.carry_over = parse_signed_duration(value)
+                .ok_or_else(|| invalid("reports.worktime.carry_over", value, "a duration"))?;
+        }
+        Ok(worktime)
+    }
+
+    /// The target time on the given day, zero if it is not worked according to the schedule
+    pub fn target(&self, date: NaiveDate) -> Duration {
+        if self.schedule.is_working_day(date) {
+            self.targets[date.weekday().num_days_from_monday() as usize]
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
+/// A duration which may start with `-`
+fn parse_signed_duration(value: &str) -> Option<Duration> {
+    match value.trim().strip_prefix('-') {
+        Some(value) => parse_duration(value).map(|duration| -duration),
+        None => parse_duration(value),
+    }
+}
+
+/// The tracked and the target time of a day, with the balance at its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayBalance {
+    /// The day in the timezone of the report
+    pub date: NaiveDate,
+    /// The target time of the day
+    pub target: Duration,
+    /// The time tracked on the day
+    pub tracked: Duration,
+    /// The working time of the schedule within the range on the day, in real time
+    pub expected: Duration,
+    /// Overtime if positive, undertime if negative
+    pub difference: Duration,
+    /// The balance at the end of the day, including the carry-over
+    pub balance: Duration,
+}
+
+impl TimewarriorData {
+    /// The balance of tracked and target time for every day within the report range
+    ///
+    /// Days are taken in the given timezone. The range starts with the report range or with the
+    /// first session and ends with the report range, but not after the current day of the
+    /// clock, so the targets of future days are not counted yet. Days without tracked time are
+    /// included, as their target still counts. The expected time of each day is taken from
+    /// [`Schedule::expected_time`] for the part of the day within the range.
+    pub fn worktime_balance<Tz: TimeZone>(
+        &self,
+        worktime: &WorkTime,
+        clock: &dyn Clock,
+        timezone: &Tz,
+    ) -> Result<Vec<DayBalance>, ReportError> {
+        let now = clock.now();
+        let start = match self.config.report_start()? {
+            Some(start) => Some(start),
+            None => self.sessions.iter().map(|session| session.start).min(),
+        };
+        let end = match self.config.report_end()? {
+            Some(end) => end.min(now),
+            None => now,
+        };
+        let (start, first, last) = match start {
+            Some(start) if start < end => (
+                start,
+                local_date(start, timezone),
+                local_date(end - Duration::nanoseconds(1), timezone),
+            ),
+            _ => return Ok(Vec::new()),
+        };
+        let tracked = self.duration_by_day(clock, timezone)?;
+        let mut balance = worktime.carry_over;
+        let mut days = Vec::new();
+        let mut date = first;
+        while date <= last {
+            let target = worktime.target(date);
+            let tracked = tracked.get(&date).copied().unwrap_or_else(Duration::zero);
+            balance = balance + tracked - target;
+            let day = DateRange::new(
+                start_of_day(date, timezone).max(start),
+                start_of_day(date.succ(), timezone).min(end),
+            );
+            days.push(DayBalance {
+                date,
+                target,
+                tracked,
+                expected: worktime.schedule.expected_time(day, timezone),
+                difference: tracked - target,
+                balance,
+            });
+            date = date.succ();
+        }
+        Ok(days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FixedClock, ReportTimeZone};
+    use chrono::Utc;
+
+    fn worktime() -> WorkTime {
+        let mut worktime = WorkTime {
+            carry_over: Duration::hours(-1),
+            ..WorkTime::default()
+        };
+        worktime.targets[..5].copy_from_slice(&[Duration::hours(8); 5]);
+        worktime
+    }
+
+    #[test]
+    fn balance_per_day() {
+        // Friday to Monday, the Monday is still running
+        let data = TimewarriorData::from_string(
+            "temp.report.start: 20210709T000000Z\n\
+             temp.report.end: 20210713T000000Z\n\
+             \n\
+             [\n\
+             {\"id\":3,\"start\":\"20210709T080000Z\",\"end\":\"20210709T173000Z\",\"tags\":[]},\n\
+             {\"id\":2,\"start\":\"20210710T100000Z\",\"end\":\"20210710T110000Z\",\"tags\":[]},\n\
+             {\"id\":1,\"start\":\"20210712T080000Z\",\"tags\":[]}\n\
+             ]"
+            .into(),
+        )
+        .unwrap();
+        let clock = FixedClock(Utc.ymd(2021, 7, 12).and_hms(14, 0, 0));
+        let days = data
+            .worktime_balance(&worktime(), &clock, &ReportTimeZone::Utc)
+            .unwrap();
+        let minutes = |minutes| Duration::minutes(minutes);
+        assert_eq!(
+            days.iter()
+                .map(|day| (day.date.day(), day.difference, day.balance))
+                .collect::<Vec<(u32, Duration, Duration)>>(),
+            vec![
+                (9, minutes(90), minutes(30)),
+                (10, minutes(60), minutes(90)),
+                (11, Duration::zero(), minutes(90)),
+                (12, minutes(-120), minutes(-30)),
+            ]
+        );
+        assert_eq!(days[3].tracked, Duration::hours(6));
+        assert_eq!(days[0].expected, Duration::hours(24));
+        assert_eq!(days[3].expected, Duration::hours(14));
+    }
+
+    #[test]
+    fn read_worktime_from_config() {
+        let config = [
+            ("reports.worktime.monday", "8h"),
+            ("reports.worktime.friday", "6:30"),
+            ("reports.worktime.carry_over", "-1h30min"),
+            ("exclusions.tuesday", "<9:00 >13:00"),
+            ("holidays.de-DE.2021_07_12", "Betriebsausflug"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<ReportConfig>();
+        let worktime = WorkTime::from_config(&config).unwrap();
+        assert_eq!(worktime.targets[0], Duration::hours(8));
+        assert_eq!(worktime.targets[1], Duration::hours(4));
+        assert_eq!(worktime.targets[2], Duration::zero());
+        assert_eq!(
+            worktime.target(NaiveDate::from_ymd(2021, 7, 12)),
+            Duration::zero()
+        );
+        assert_eq!(
+            worktime.target(NaiveDate::from_ymd(2021, 7, 19)),
+            Duration::hours(8)
+        );
+        assert_eq!(worktime.targets[4], Duration::minutes(390));
+        assert_eq!(worktime.carry_over, Duration::minutes(-90));
+
+        let mut config = ReportConfig::default();
+        config.insert("reports.worktime.carry_over".into(), "lots".into());
+        assert!(WorkTime::from_config(&config).is_err());
+    }
+}